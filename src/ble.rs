@@ -9,7 +9,10 @@ use trouble_host::{
     prelude::*,
 };
 
-use crate::progressor::{DataPoint, MAX_PAYLOAD_SIZE};
+use crate::{
+    progressor::{DataPoint, MAX_PAYLOAD_SIZE},
+    transfer::{Chunk, TRANSFER_CONTROL_SIZE},
+};
 
 /// Max number of connections
 pub const CONNECTIONS_MAX: usize = 1;
@@ -44,6 +47,7 @@ const SCAN_RESPONSE_DATA: &[u8] = &[
 #[gatt_server]
 pub struct Server {
     pub progressor: ProgressorService,
+    pub bulk_transfer: BulkTransferService,
 }
 
 /// Tindeq Progressor service
@@ -62,6 +66,23 @@ pub struct ProgressorService {
     pub control_point: [u8; MAX_PAYLOAD_SIZE], // Buffer for command data
 }
 
+/// Bulk transfer service, for stored data beyond a single data point
+// Custom service, no part of Tindeq API
+#[gatt_service(uuid = "5c3a0001-7d2e-4b8f-9a61-3e0f2c8d4b17")]
+pub struct BulkTransferService {
+    /// Transfer Data - chunks of the resource being transferred
+    #[characteristic(uuid = "5c3a0002-7d2e-4b8f-9a61-3e0f2c8d4b17", notify)]
+    pub transfer_data: Chunk,
+
+    /// Transfer Control - for starting, acknowledging and aborting transfers
+    #[characteristic(
+        uuid = "5c3a0003-7d2e-4b8f-9a61-3e0f2c8d4b17",
+        write,
+        write_without_response
+    )]
+    pub transfer_control: [u8; TRANSFER_CONTROL_SIZE],
+}
+
 /// Create an advertiser to use to connect to a BLE Central, and wait for it to connect.
 pub async fn advertise<'values, 'server, C: Controller>(
     name: &'values str,
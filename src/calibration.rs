@@ -0,0 +1,651 @@
+/// Calibration models
+///
+/// Map tared raw HX711 readings to weight. The model is fitted on the calibration points,
+/// using the most flexible model the number of points supports:
+///
+/// - Up to 3 points: slope, fitted by least squares.
+/// - 4 or 5 points: quadratic, fitted by least squares.
+/// - 6 points or more: piecewise-linear curve through the points.
+///
+/// The raw values are tared, so the tare value is the zero of every model: the least
+/// squares fits go through it and the piecewise-linear curve is shifted to read zero
+/// there. The computations have no hardware dependencies.
+use defmt::Format;
+
+/// Maximum number of calibration points to store
+pub const MAX_CALIBRATION_POINTS: usize = 20;
+/// Minimum number of valid points for a fit, and so for a linear one
+pub const MIN_CALIBRATION_POINTS: usize = 2;
+/// Minimum number of valid points for a quadratic fit
+const QUADRATIC_MIN_POINTS: usize = 4;
+/// Minimum number of valid points for a piecewise-linear curve
+const PIECEWISE_MIN_POINTS: usize = 6;
+/// Minimum distance between the raw values of two points, in counts
+const MIN_RAW_DISTANCE: f64 = 1.0;
+/// Design load (full scale), in kg
+const FULL_SCALE: f32 = 150.0;
+/// Smallest recommended weight span of the points, as a fraction of the full scale
+const MIN_SPAN_FRACTION: f32 = 0.5;
+/// Smallest recommended weight difference between two points, in kg
+const MIN_POINT_SPACING: f32 = 1.0;
+/// Residuals above this multiple of the median absolute residual can be outliers
+const OUTLIER_RATIO: f32 = 3.0;
+
+/// Calibration point storing tared raw value and known weight
+pub type CalibrationPoint = (f32, f32);
+
+/// Calibration model type
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+#[repr(u8)]
+pub enum ModelKind {
+    /// Slope through the tare value
+    Linear = 0,
+    /// Quadratic polynomial
+    Quadratic = 1,
+    /// Piecewise-linear curve through the points
+    Piecewise = 2,
+}
+
+/// Calibration model, mapping raw readings to weight
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalibrationModel {
+    /// Scale factor, in grams per raw count
+    Linear(f32),
+    /// Quadratic through the tare value, in kg:
+    /// weight = raw · (slope + curvature · (raw − 2 · origin)), `slope` being the gradient
+    /// at `origin`
+    Quadratic {
+        origin: f32,
+        slope: f32,
+        curvature: f32,
+    },
+    /// Curve through the first `len` (raw value, weight in kg) points, sorted by raw value
+    Piecewise {
+        points: [CalibrationPoint; MAX_CALIBRATION_POINTS],
+        len: usize,
+    },
+}
+
+/// Error fitting a calibration model
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub enum CalibrationError {
+    /// Fewer than two valid points
+    NotEnoughPoints,
+    /// The raw values of the points are too close together
+    PointsTooClose,
+}
+
+/// Fitted calibration model and how well it matches the points
+#[derive(Clone, Copy, Debug)]
+pub struct CalibrationFit {
+    /// Fitted model
+    pub model: CalibrationModel,
+    /// Weight read by the model minus the reference weight of each point in kg, NaN for
+    /// skipped points
+    ///
+    /// The piecewise-linear curve goes through every point, so each residual is taken
+    /// from the curve through the other points instead, shifted to zero at the tare
+    /// value as it would be applied.
+    pub residuals: [f32; MAX_CALIBRATION_POINTS],
+}
+
+/// Calibration quality report
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct CalibrationReport {
+    /// Coefficient of determination (R²) of the fit
+    pub r_squared: f32,
+    /// Largest absolute residual, in kg
+    pub max_error: f32,
+    /// Warning flags, see the associated constants
+    pub warnings: u8,
+    /// Outlier point, bit `n` set for point `n`
+    pub outliers: u32,
+    /// Every residual within the rated precision and no outliers
+    pub passed: bool,
+}
+
+impl CalibrationReport {
+    /// Two points are less than [`MIN_POINT_SPACING`] apart
+    pub const POINTS_TOO_CLOSE: u8 = 1 << 0;
+    /// The points span less than [`MIN_SPAN_FRACTION`] of the full scale
+    pub const NARROW_SPAN: u8 = 1 << 1;
+    /// A residual exceeds the rated precision
+    pub const PRECISION_EXCEEDED: u8 = 1 << 2;
+    /// Some points are outliers
+    pub const OUTLIERS: u8 = 1 << 3;
+}
+
+impl CalibrationFit {
+    /// Evaluate the fit of `points`, the points it was fitted on.
+    ///
+    /// Every figure comes from the residuals, so the report grades the model as it is
+    /// applied to the readings.
+    pub fn report(&self, points: &[CalibrationPoint]) -> CalibrationReport {
+        let len = points.len().min(MAX_CALIBRATION_POINTS);
+        let fitted = || {
+            points[..len]
+                .iter()
+                .zip(&self.residuals[..len])
+                .enumerate()
+                .filter(|(_, (_, residual))| residual.is_finite())
+                .map(|(index, ((_, weight), residual))| (index, *weight, *residual))
+        };
+
+        let count = fitted().count();
+        let mean_weight = fitted().map(|(_, weight, _)| weight).sum::<f32>() / count as f32;
+        let ss_res: f32 = fitted().map(|(_, _, residual)| residual * residual).sum();
+        let ss_tot: f32 = fitted()
+            .map(|(_, weight, _)| (weight - mean_weight) * (weight - mean_weight))
+            .sum();
+        let r_squared = if ss_tot > 0.0 {
+            1.0 - ss_res / ss_tot
+        } else {
+            0.0
+        };
+        let max_error = fitted()
+            .map(|(_, _, residual)| residual.abs())
+            .fold(0.0, f32::max);
+
+        let mut warnings = 0;
+        let (min_weight, max_weight) = fitted().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), (_, weight, _)| (min.min(weight), max.max(weight)),
+        );
+        if max_weight - min_weight < FULL_SCALE * MIN_SPAN_FRACTION {
+            warnings |= CalibrationReport::NARROW_SPAN;
+        }
+        if fitted()
+            .any(|(i, a, _)| fitted().any(|(j, b, _)| i != j && (a - b).abs() < MIN_POINT_SPACING))
+        {
+            warnings |= CalibrationReport::POINTS_TOO_CLOSE;
+        }
+        if fitted().any(|(_, weight, residual)| residual.abs() > precision(weight)) {
+            warnings |= CalibrationReport::PRECISION_EXCEEDED;
+        }
+
+        // A bad point also shifts the residuals of the others, so only the worst point is
+        // flagged, when it is out of precision and stands out from the other residuals
+        let mut errors = [0.0; MAX_CALIBRATION_POINTS];
+        for (error, (_, _, residual)) in errors.iter_mut().zip(fitted()) {
+            *error = residual.abs();
+        }
+        let errors = &mut errors[..count];
+        errors.sort_unstable_by(f32::total_cmp);
+        let median_error = errors.get(count / 2).copied().unwrap_or(0.0);
+        let mut outliers = 0;
+        if let Some((index, weight, residual)) =
+            fitted().max_by(|a, b| a.2.abs().total_cmp(&b.2.abs()))
+            && residual.abs() > precision(weight)
+            && residual.abs() > OUTLIER_RATIO * median_error
+        {
+            outliers |= 1 << index;
+        }
+        if outliers != 0 {
+            warnings |= CalibrationReport::OUTLIERS;
+        }
+
+        CalibrationReport {
+            r_squared,
+            max_error,
+            warnings,
+            outliers,
+            passed: warnings
+                & (CalibrationReport::PRECISION_EXCEEDED | CalibrationReport::OUTLIERS)
+                == 0,
+        }
+    }
+}
+
+/// Rated precision at `weight`, in kg
+pub fn precision(weight: f32) -> f32 {
+    if weight < 100.0 { 0.05 } else { 0.1 }
+}
+
+impl CalibrationModel {
+    /// Size of the serialized model in bytes
+    pub const SIZE: usize = 2 + 8 * MAX_CALIBRATION_POINTS;
+
+    /// Get the model type
+    pub fn kind(&self) -> ModelKind {
+        match self {
+            CalibrationModel::Linear(..) => ModelKind::Linear,
+            CalibrationModel::Quadratic { .. } => ModelKind::Quadratic,
+            CalibrationModel::Piecewise { .. } => ModelKind::Piecewise,
+        }
+    }
+
+    /// Weight in kg of a tared raw reading
+    pub fn weight(&self, raw_tared: f32) -> f32 {
+        match self {
+            // Convert from grams
+            CalibrationModel::Linear(factor) => raw_tared * factor / 1000.0,
+            CalibrationModel::Quadratic {
+                origin,
+                slope,
+                curvature,
+            } => raw_tared * (slope + curvature * (raw_tared - 2.0 * origin)),
+            CalibrationModel::Piecewise { points, len } => {
+                let points = &points[..*len];
+                evaluate_piecewise(points, raw_tared) - evaluate_piecewise(points, 0.0)
+            }
+        }
+    }
+
+    /// Scale factor around zero, in grams per raw count
+    pub fn factor(&self) -> f32 {
+        match self {
+            CalibrationModel::Linear(factor) => *factor,
+            CalibrationModel::Quadratic {
+                origin,
+                slope,
+                curvature,
+            } => (slope - 2.0 * curvature * origin) * 1000.0,
+            CalibrationModel::Piecewise { points, len } => {
+                let (a, b) = segment(&points[..*len], 0.0);
+                (b.1 - a.1) / (b.0 - a.0) * 1000.0
+            }
+        }
+    }
+
+    /// Check if the model can be used to convert readings
+    pub fn is_valid(&self) -> bool {
+        match self {
+            CalibrationModel::Linear(factor) => factor.is_finite() && *factor != 0.0,
+            CalibrationModel::Quadratic {
+                origin,
+                slope,
+                curvature,
+            } => origin.is_finite() && slope.is_finite() && curvature.is_finite() && *slope != 0.0,
+            CalibrationModel::Piecewise { points, len } => {
+                (2..=MAX_CALIBRATION_POINTS).contains(len)
+                    && points[..*len]
+                        .iter()
+                        .all(|(raw, weight)| raw.is_finite() && weight.is_finite())
+                    && points[..*len]
+                        .windows(2)
+                        .all(|pair| pair[1].0 - pair[0].0 >= MIN_RAW_DISTANCE as f32)
+            }
+        }
+    }
+
+    /// Serialize as model type, point count and little-endian parameters
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = self.kind() as u8;
+        match &self {
+            CalibrationModel::Linear(factor) => {
+                bytes[2..6].copy_from_slice(&factor.to_le_bytes());
+            }
+            CalibrationModel::Quadratic {
+                origin,
+                slope,
+                curvature,
+            } => {
+                bytes[2..6].copy_from_slice(&origin.to_le_bytes());
+                bytes[6..10].copy_from_slice(&slope.to_le_bytes());
+                bytes[10..14].copy_from_slice(&curvature.to_le_bytes());
+            }
+            CalibrationModel::Piecewise { points, len } => {
+                bytes[1] = *len as u8;
+                for (i, (raw, weight)) in points[..*len].iter().enumerate() {
+                    let offset = 2 + 8 * i;
+                    bytes[offset..offset + 4].copy_from_slice(&raw.to_le_bytes());
+                    bytes[offset + 4..offset + 8].copy_from_slice(&weight.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Deserialize a model, returning None if it is invalid
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let f32_at = |offset: usize| {
+            f32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let model = match bytes[0] {
+            0 => CalibrationModel::Linear(f32_at(2)),
+            1 => CalibrationModel::Quadratic {
+                origin: f32_at(2),
+                slope: f32_at(6),
+                curvature: f32_at(10),
+            },
+            2 => {
+                let len = (bytes[1] as usize).min(MAX_CALIBRATION_POINTS);
+                let mut points = [(0.0, 0.0); MAX_CALIBRATION_POINTS];
+                for (i, point) in points[..len].iter_mut().enumerate() {
+                    *point = (f32_at(2 + 8 * i), f32_at(6 + 8 * i));
+                }
+                CalibrationModel::Piecewise { points, len }
+            }
+            _ => return None,
+        };
+        model.is_valid().then_some(model)
+    }
+}
+
+/// Valid calibration point, with its index in the calibration points
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    raw: f64,
+    weight: f64,
+    index: usize,
+}
+
+/// Fit the calibration model supported by the number of valid points.
+///
+/// Points with a non-finite raw value or a negative or non-finite weight are skipped.
+pub fn fit(points: &[CalibrationPoint]) -> Result<CalibrationFit, CalibrationError> {
+    let mut samples = [Sample {
+        raw: 0.0,
+        weight: 0.0,
+        index: 0,
+    }; MAX_CALIBRATION_POINTS];
+    let mut len = 0;
+    for (index, (raw, weight)) in points.iter().take(MAX_CALIBRATION_POINTS).enumerate() {
+        if raw.is_finite() && weight.is_finite() && *weight >= 0.0 {
+            samples[len] = Sample {
+                raw: *raw as f64,
+                weight: *weight as f64,
+                index,
+            };
+            len += 1;
+        }
+    }
+    let samples = &mut samples[..len];
+    if samples.len() < MIN_CALIBRATION_POINTS {
+        return Err(CalibrationError::NotEnoughPoints);
+    }
+
+    let model = if samples.len() >= PIECEWISE_MIN_POINTS
+        && let Some(model) = fit_piecewise(samples)
+    {
+        model
+    } else if samples.len() >= QUADRATIC_MIN_POINTS
+        && let Some(model) = fit_quadratic(samples)
+    {
+        model
+    } else {
+        fit_linear(samples)?
+    };
+
+    // Grade the model as applied to the readings
+    let mut residuals = [f32::NAN; MAX_CALIBRATION_POINTS];
+    for sample in samples.iter() {
+        let applied = match model {
+            // The curve goes through every point, grade it by the curve through the others
+            CalibrationModel::Piecewise { .. } => {
+                piecewise(samples.iter().filter(|other| other.index != sample.index))
+            }
+            _ => model,
+        };
+        residuals[sample.index] = applied.weight(sample.raw as f32) - sample.weight as f32;
+    }
+
+    Ok(CalibrationFit { model, residuals })
+}
+
+/// Least-squares line through the tare value and the samples
+fn fit_linear(samples: &[Sample]) -> Result<CalibrationModel, CalibrationError> {
+    let n = samples.len() as f64;
+    let mean_raw = samples.iter().map(|s| s.raw).sum::<f64>() / n;
+    let spread: f64 = samples
+        .iter()
+        .map(|s| (s.raw - mean_raw) * (s.raw - mean_raw))
+        .sum();
+    if spread < MIN_RAW_DISTANCE * MIN_RAW_DISTANCE {
+        return Err(CalibrationError::PointsTooClose);
+    }
+
+    let sum_xx: f64 = samples.iter().map(|s| s.raw * s.raw).sum();
+    let sum_xy: f64 = samples.iter().map(|s| s.raw * s.weight).sum();
+    // Grams per raw count
+    Ok(CalibrationModel::Linear((sum_xy / sum_xx * 1000.0) as f32))
+}
+
+/// Least-squares quadratic through the tare value and the samples, None if it is
+/// degenerate
+fn fit_quadratic(samples: &[Sample]) -> Option<CalibrationModel> {
+    // Scale the raw values to keep the normal equations well conditioned
+    let scale = samples.iter().map(|s| s.raw.abs()).fold(0.0, f64::max);
+    if scale < MIN_RAW_DISTANCE {
+        return None;
+    }
+
+    // Normal equations for weight = b·u + c·u²
+    let mut sums = [0.0; 3];
+    let mut rhs = [0.0; 2];
+    for sample in samples {
+        let u = sample.raw / scale;
+        sums[0] += u * u;
+        sums[1] += u * u * u;
+        sums[2] += u * u * u * u;
+        rhs[0] += u * sample.weight;
+        rhs[1] += u * u * sample.weight;
+    }
+    let determinant = sums[0] * sums[2] - sums[1] * sums[1];
+    if determinant.abs() <= f64::EPSILON * sums[0] * sums[2] {
+        return None;
+    }
+    let b = (rhs[0] * sums[2] - rhs[1] * sums[1]) / determinant / scale;
+    let c = (sums[0] * rhs[1] - sums[1] * rhs[0]) / determinant / (scale * scale);
+
+    // Keep the gradient at the mean raw value, where it is the most meaningful
+    let origin = samples.iter().map(|s| s.raw).sum::<f64>() / samples.len() as f64;
+    Some(CalibrationModel::Quadratic {
+        origin: origin as f32,
+        slope: (b + 2.0 * c * origin) as f32,
+        curvature: c as f32,
+    })
+}
+
+/// Piecewise-linear curve through the samples, None if two raw values are too close
+fn fit_piecewise(samples: &mut [Sample]) -> Option<CalibrationModel> {
+    samples.sort_unstable_by(|a, b| a.raw.total_cmp(&b.raw));
+    if samples
+        .windows(2)
+        .any(|pair| pair[1].raw - pair[0].raw < MIN_RAW_DISTANCE)
+    {
+        return None;
+    }
+    Some(piecewise(samples.iter()))
+}
+
+/// Piecewise-linear curve through samples sorted by raw value
+fn piecewise<'a>(samples: impl Iterator<Item = &'a Sample>) -> CalibrationModel {
+    let mut points = [(0.0, 0.0); MAX_CALIBRATION_POINTS];
+    let mut len = 0;
+    for (point, sample) in points.iter_mut().zip(samples) {
+        *point = (sample.raw as f32, sample.weight as f32);
+        len += 1;
+    }
+    CalibrationModel::Piecewise { points, len }
+}
+
+/// Segment of the curve used at `raw`, extending the first and last segments
+fn segment(points: &[CalibrationPoint], raw: f32) -> (CalibrationPoint, CalibrationPoint) {
+    let i = points[1..points.len() - 1]
+        .iter()
+        .take_while(|(point_raw, _)| *point_raw < raw)
+        .count();
+    (points[i], points[i + 1])
+}
+
+/// Weight of the piecewise-linear curve at `raw`
+fn evaluate_piecewise(points: &[CalibrationPoint], raw: f32) -> f32 {
+    let (a, b) = segment(points, raw);
+    a.1 + (b.1 - a.1) * (raw - a.0) / (b.0 - a.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points at `weights` in kg, read through `raw` from weight to raw value
+    fn points<const N: usize>(
+        weights: [f32; N],
+        raw: impl Fn(f32) -> f32,
+    ) -> [CalibrationPoint; N] {
+        weights.map(|weight| (raw(weight), weight))
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn linear_fit_recovers_exact_line() {
+        // 10 g per count
+        let points = points([20.0, 60.0, 120.0], |weight| weight * 100.0);
+        let fit = fit(&points).unwrap();
+        let CalibrationModel::Linear(factor) = fit.model else {
+            panic!("linear model expected, got {:?}", fit.model);
+        };
+        assert_close(factor, 10.0, 1e-5);
+        for residual in &fit.residuals[..3] {
+            assert_close(*residual, 0.0, 1e-4);
+        }
+        assert!(fit.residuals[3..].iter().all(|residual| residual.is_nan()));
+
+        let report = fit.report(&points);
+        assert!(report.passed);
+        assert_eq!(report.warnings, 0);
+        assert_close(report.r_squared, 1.0, 1e-6);
+    }
+
+    #[test]
+    fn intercept_shows_in_residuals() {
+        // Tared raw values still reading 2 kg at zero, the model goes through the tare
+        let points = points([20.0, 60.0, 120.0], |weight| (weight - 2.0) * 100.0);
+        let fit = fit(&points).unwrap();
+        for (residual, (raw, weight)) in fit.residuals.iter().zip(&points) {
+            assert_eq!(*residual, fit.model.weight(*raw) - weight);
+        }
+        assert_eq!(fit.model.weight(0.0), 0.0);
+
+        let report = fit.report(&points);
+        assert!(!report.passed);
+        assert_ne!(report.warnings & CalibrationReport::PRECISION_EXCEEDED, 0);
+        assert!(report.max_error > 0.5);
+    }
+
+    #[test]
+    fn quadratic_fit_recovers_curve() {
+        // Bent readings: weight = raw / 100 + raw² / 1e7
+        let points =
+            [1000.0, 4000.0, 8000.0, 12000.0].map(|raw: f32| (raw, raw / 100.0 + raw * raw / 1e7));
+        let fit = fit(&points).unwrap();
+        assert_eq!(fit.model.kind(), ModelKind::Quadratic);
+        for ((raw, weight), residual) in points.iter().zip(&fit.residuals) {
+            assert_close(fit.model.weight(*raw), *weight, 1e-3);
+            assert_close(*residual, 0.0, 1e-3);
+        }
+        assert_close(fit.model.weight(0.0), 0.0, 1e-6);
+        assert_close(fit.model.factor(), 10.0, 1e-3);
+    }
+
+    #[test]
+    fn model_follows_point_count() {
+        let weights = [5.0, 20.0, 40.0, 60.0, 80.0, 100.0, 120.0];
+        let raw = |weight: f32| weight * 100.0;
+        assert_eq!(
+            fit(&points([5.0, 20.0, 40.0], raw)).unwrap().model.kind(),
+            ModelKind::Linear
+        );
+        assert_eq!(
+            fit(&points([5.0, 20.0, 40.0, 60.0, 80.0], raw))
+                .unwrap()
+                .model
+                .kind(),
+            ModelKind::Quadratic
+        );
+        let fit = fit(&points(weights, raw)).unwrap();
+        assert_eq!(fit.model.kind(), ModelKind::Piecewise);
+        // Each point is graded by the curve through the others, exact on a line
+        for residual in &fit.residuals[..weights.len()] {
+            assert_close(*residual, 0.0, 1e-3);
+        }
+    }
+
+    #[test]
+    fn piecewise_falls_back_to_quadratic_when_points_are_too_close() {
+        let mut points = points([5.0, 20.0, 40.0, 60.0, 80.0, 100.0], |weight| {
+            weight * 100.0
+        });
+        points[1].0 = points[0].0 + 0.5;
+        assert_eq!(fit(&points).unwrap().model.kind(), ModelKind::Quadratic);
+    }
+
+    #[test]
+    fn points_too_close_are_rejected() {
+        assert_eq!(
+            fit(&[(500.0, 5.0), (500.5, 5.0)]).unwrap_err(),
+            CalibrationError::PointsTooClose
+        );
+        assert_eq!(
+            fit(&[(500.0, 5.0), (f32::NAN, 10.0), (1000.0, -1.0)]).unwrap_err(),
+            CalibrationError::NotEnoughPoints
+        );
+    }
+
+    #[test]
+    fn outlier_is_flagged() {
+        let mut points = points([5.0, 20.0, 35.0, 50.0, 65.0, 80.0, 95.0, 110.0], |weight| {
+            weight * 100.0
+        });
+        points[4].1 += 1.0;
+        let report = fit(&points).unwrap().report(&points);
+        assert_eq!(report.outliers, 1 << 4);
+        assert_ne!(report.warnings & CalibrationReport::OUTLIERS, 0);
+        assert!(!report.passed);
+    }
+
+    #[test]
+    fn close_points_and_narrow_span_only_warn() {
+        let points = points([10.0, 10.5, 30.0], |weight| weight * 100.0);
+        let report = fit(&points).unwrap().report(&points);
+        assert_eq!(
+            report.warnings,
+            CalibrationReport::POINTS_TOO_CLOSE | CalibrationReport::NARROW_SPAN
+        );
+        assert!(report.passed);
+    }
+
+    #[test]
+    fn model_round_trips_through_bytes() {
+        let mut points = [(0.0, 0.0); MAX_CALIBRATION_POINTS];
+        points[..3].copy_from_slice(&[(-100.0, -1.0), (0.0, 0.0), (5000.0, 50.0)]);
+        let models = [
+            CalibrationModel::Linear(10.0),
+            CalibrationModel::Quadratic {
+                origin: 5000.0,
+                slope: 0.011,
+                curvature: 1e-7,
+            },
+            CalibrationModel::Piecewise { points, len: 3 },
+        ];
+        for model in models {
+            assert_eq!(CalibrationModel::from_bytes(&model.to_bytes()), Some(model));
+        }
+    }
+
+    #[test]
+    fn invalid_model_bytes_are_rejected() {
+        let mut bytes = CalibrationModel::Linear(0.0).to_bytes();
+        assert_eq!(CalibrationModel::from_bytes(&bytes), None);
+        bytes[0] = 3;
+        assert_eq!(CalibrationModel::from_bytes(&bytes), None);
+
+        let mut points = [(0.0, 0.0); MAX_CALIBRATION_POINTS];
+        points[..2].copy_from_slice(&[(100.0, 1.0), (100.5, 2.0)]);
+        let unsorted = CalibrationModel::Piecewise { points, len: 2 };
+        assert_eq!(CalibrationModel::from_bytes(&unsorted.to_bytes()), None);
+    }
+}
@@ -0,0 +1,261 @@
+/// Critical force test
+///
+/// All-out intermittent test of 24 reps, each 7 s of maximal pulling followed by 3 s of
+/// rest. The client is cued at every phase boundary and sent the mean force of each rep.
+/// The critical force is the mean force of the last reps, once the athlete is exhausted,
+/// and W' is the impulse of all reps above the critical force.
+use arrayvec::ArrayVec;
+use defmt::Format;
+use embassy_time::{Duration, Instant};
+
+use crate::filter::mean;
+
+/// Number of reps in the test
+pub const REP_COUNT: usize = 24;
+/// Duration of the work phase of a rep
+pub const WORK_TIME: Duration = Duration::from_secs(7);
+/// Duration of the rest phase of a rep
+pub const REST_TIME: Duration = Duration::from_secs(3);
+/// Number of final reps averaged into the critical force
+const CRITICAL_FORCE_REPS: usize = 6;
+/// Maximum number of events caused by a single sample
+const MAX_EVENTS: usize = 2;
+
+/// Phase of a rep
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+#[repr(u8)]
+pub enum TestPhase {
+    /// Pull as hard as possible
+    Work = 0,
+    /// Release the load
+    Rest = 1,
+}
+
+impl TestPhase {
+    /// Duration of the phase
+    pub fn duration(self) -> Duration {
+        match self {
+            TestPhase::Work => WORK_TIME,
+            TestPhase::Rest => REST_TIME,
+        }
+    }
+}
+
+/// Critical force test outcome
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct CriticalForceResult {
+    /// Critical force, in kg
+    pub critical_force: f32,
+    /// Work capacity above the critical force, in kg·s
+    pub w_prime: f32,
+}
+
+/// Progress of the test to report to the client
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub enum TestEvent {
+    /// A phase of the rep with the given index starts now
+    Cue(u8, TestPhase),
+    /// Mean force in kg of the work phase of the rep with the given index
+    RepMean(u8, f32),
+    /// The test is completed
+    Completed(CriticalForceResult),
+}
+
+/// Critical force test state
+#[derive(Clone, Copy, Debug)]
+pub struct CriticalForceTest {
+    /// Start of the first rep, None until the first sample
+    start: Option<Instant>,
+    /// Index of the current rep
+    rep: usize,
+    /// Phase of the current rep
+    phase: TestPhase,
+    /// Sum of the samples of the current work phase
+    sum: f32,
+    /// Number of samples of the current work phase
+    count: u32,
+    /// Mean force of the completed reps
+    means: [f32; REP_COUNT],
+    /// The last rep was completed
+    finished: bool,
+}
+
+impl Default for CriticalForceTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CriticalForceTest {
+    /// Create a test starting with the next sample
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            rep: 0,
+            phase: TestPhase::Work,
+            sum: 0.0,
+            count: 0,
+            means: [0.0; REP_COUNT],
+            finished: false,
+        }
+    }
+
+    /// Check if the last rep was completed
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Add a sample taken at `time`, returning the events it causes
+    pub fn update(&mut self, weight: f32, time: Instant) -> ArrayVec<TestEvent, MAX_EVENTS> {
+        let mut events = ArrayVec::new();
+        if self.finished {
+            return events;
+        }
+        let Some(start) = self.start else {
+            self.start = Some(time);
+            events.push(TestEvent::Cue(0, TestPhase::Work));
+            self.add_sample(weight);
+            return events;
+        };
+
+        // Phase boundaries are fixed from the start, so late samples do not shift them
+        let rep_time = WORK_TIME + REST_TIME;
+        let phase_end = start
+            + rep_time * self.rep as u32
+            + match self.phase {
+                TestPhase::Work => WORK_TIME,
+                TestPhase::Rest => rep_time,
+            };
+        if time < phase_end {
+            self.add_sample(weight);
+            return events;
+        }
+
+        let rep = self.rep as u8;
+        match self.phase {
+            TestPhase::Work => {
+                let rep_mean = if self.count > 0 {
+                    self.sum / self.count as f32
+                } else {
+                    0.0
+                };
+                self.means[self.rep] = rep_mean;
+                events.push(TestEvent::RepMean(rep, rep_mean));
+
+                if self.rep + 1 == REP_COUNT {
+                    self.finished = true;
+                    events.push(TestEvent::Completed(self.result()));
+                } else {
+                    self.phase = TestPhase::Rest;
+                    events.push(TestEvent::Cue(rep, TestPhase::Rest));
+                }
+            }
+            TestPhase::Rest => {
+                self.rep += 1;
+                self.phase = TestPhase::Work;
+                self.sum = 0.0;
+                self.count = 0;
+                self.add_sample(weight);
+                events.push(TestEvent::Cue(rep + 1, TestPhase::Work));
+            }
+        }
+        events
+    }
+
+    /// Accumulate a sample of the work phase
+    fn add_sample(&mut self, weight: f32) {
+        if self.phase == TestPhase::Work {
+            self.sum += weight;
+            self.count += 1;
+        }
+    }
+
+    /// Compute the critical force and W' from the rep means
+    fn result(&self) -> CriticalForceResult {
+        let last_reps = &self.means[REP_COUNT - CRITICAL_FORCE_REPS..];
+        let critical_force = mean(last_reps);
+        let work_seconds = WORK_TIME.as_millis() as f32 / 1000.0;
+        let w_prime = self
+            .means
+            .iter()
+            .map(|rep_mean| (rep_mean - critical_force).max(0.0) * work_seconds)
+            .sum();
+        CriticalForceResult {
+            critical_force,
+            w_prime,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the whole test with a sample every second, pulling `force(rep)` during the
+    /// work phases, returning the rep means and the last event
+    fn run(force: impl Fn(usize) -> f32) -> ([f32; REP_COUNT], Option<TestEvent>) {
+        let mut test = CriticalForceTest::new();
+        let mut means = [f32::NAN; REP_COUNT];
+        let mut last = None;
+        for second in 0..REP_COUNT as u64 * 10 {
+            let rep = second as usize / 10;
+            let weight = if second % 10 < 7 { force(rep) } else { 0.0 };
+            for event in test.update(weight, Instant::from_secs(second)) {
+                if let TestEvent::RepMean(rep, rep_mean) = event {
+                    means[rep as usize] = rep_mean;
+                }
+                last = Some(event);
+            }
+        }
+        assert!(test.is_finished());
+        (means, last)
+    }
+
+    #[test]
+    fn first_sample_cues_work() {
+        let mut test = CriticalForceTest::new();
+        let events = test.update(30.0, Instant::from_secs(0));
+        assert_eq!(events.as_slice(), [TestEvent::Cue(0, TestPhase::Work)]);
+    }
+
+    #[test]
+    fn work_end_reports_mean_and_cues_rest() {
+        let mut test = CriticalForceTest::new();
+        test.update(30.0, Instant::from_secs(0));
+        test.update(40.0, Instant::from_secs(3));
+        let events = test.update(0.0, Instant::from_secs(7));
+        assert_eq!(
+            events.as_slice(),
+            [
+                TestEvent::RepMean(0, 35.0),
+                TestEvent::Cue(0, TestPhase::Rest)
+            ]
+        );
+        let events = test.update(0.0, Instant::from_secs(10));
+        assert_eq!(events.as_slice(), [TestEvent::Cue(1, TestPhase::Work)]);
+    }
+
+    #[test]
+    fn critical_force_from_last_reps_and_w_prime_above_it() {
+        let (means, last) = run(|rep| if rep < 18 { 40.0 } else { 20.0 });
+        assert_eq!(means[..18], [40.0; 18]);
+        assert_eq!(means[18..], [20.0; 6]);
+        assert_eq!(
+            last,
+            Some(TestEvent::Completed(CriticalForceResult {
+                critical_force: 20.0,
+                w_prime: 18.0 * 20.0 * 7.0,
+            }))
+        );
+    }
+
+    #[test]
+    fn finished_test_ignores_samples() {
+        let mut test = CriticalForceTest::new();
+        for second in 0..REP_COUNT as u64 * 10 {
+            test.update(10.0, Instant::from_secs(second));
+        }
+        assert!(test.is_finished());
+        assert!(test.update(10.0, Instant::from_secs(1000)).is_empty());
+    }
+}
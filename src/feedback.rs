@@ -0,0 +1,189 @@
+/// Target force feedback
+///
+/// Compares the calibrated weight with a target band and reports when it moves below,
+/// into or above the band. The events are a single byte, so minimal clients such as a
+/// watch or a buzzer can give biofeedback without processing the measurement stream.
+use defmt::Format;
+use embassy_time::{Duration, Instant};
+
+/// Target band configuration
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct TargetBandConfig {
+    /// Send band events while measuring
+    pub enabled: bool,
+    /// Lower bound of the band, in kg
+    pub lower: f32,
+    /// Upper bound of the band, in kg
+    pub upper: f32,
+    /// Shortest time between two events, zero sends every change
+    pub min_interval: Duration,
+    /// Send the band events instead of the weight measurements
+    pub events_only: bool,
+}
+
+impl Default for TargetBandConfig {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl TargetBandConfig {
+    /// Configuration not sending any events
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        lower: 0.0,
+        upper: 0.0,
+        min_interval: Duration::from_ticks(0),
+        events_only: false,
+    };
+
+    /// Check if the configuration is within the supported ranges
+    pub fn is_valid(&self) -> bool {
+        self.lower.is_finite() && self.upper.is_finite() && self.lower <= self.upper
+    }
+}
+
+/// Position of the weight relative to the target band
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+#[repr(u8)]
+pub enum BandPosition {
+    /// Weight under the lower bound
+    Below = 0,
+    /// Weight within the bounds
+    In = 1,
+    /// Weight over the upper bound
+    Above = 2,
+}
+
+/// Target band tracking state
+#[derive(Clone, Copy, Debug)]
+pub struct TargetFeedback {
+    config: TargetBandConfig,
+    /// Last position sent and when
+    last_event: Option<(BandPosition, Instant)>,
+}
+
+impl TargetFeedback {
+    /// Create a target band tracker with the given configuration
+    pub fn new(config: TargetBandConfig) -> Self {
+        Self {
+            config,
+            last_event: None,
+        }
+    }
+
+    /// Get the target band configuration
+    pub fn config(&self) -> TargetBandConfig {
+        self.config
+    }
+
+    /// Forget the last position sent, e.g. when a new measurement starts
+    pub fn reset(&mut self) {
+        self.last_event = None;
+    }
+
+    /// Check if the weight measurements are sent, or only the band events
+    pub fn streams_weight(&self) -> bool {
+        !(self.config.enabled && self.config.events_only)
+    }
+
+    /// Compare a sample taken at `time` with the band, returning the position to send
+    ///
+    /// A change within the minimum interval of the last event is held back and sent
+    /// with a later sample if the position is still different then.
+    pub fn update(&mut self, weight: f32, time: Instant) -> Option<BandPosition> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let position = if weight < self.config.lower {
+            BandPosition::Below
+        } else if weight > self.config.upper {
+            BandPosition::Above
+        } else {
+            BandPosition::In
+        };
+        match self.last_event {
+            Some((last, _)) if last == position => None,
+            Some((_, sent)) if time - sent < self.config.min_interval => None,
+            _ => {
+                self.last_event = Some((position, time));
+                Some(position)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(min_interval: Duration) -> TargetFeedback {
+        TargetFeedback::new(TargetBandConfig {
+            enabled: true,
+            lower: 10.0,
+            upper: 20.0,
+            min_interval,
+            events_only: false,
+        })
+    }
+
+    #[test]
+    fn reports_position_changes_only() {
+        let mut feedback = feedback(Duration::from_ticks(0));
+        let positions = [5.0, 8.0, 15.0, 20.0, 25.0, 5.0]
+            .map(|weight| feedback.update(weight, Instant::from_millis(0)));
+        assert_eq!(
+            positions,
+            [
+                Some(BandPosition::Below),
+                None,
+                Some(BandPosition::In),
+                None,
+                Some(BandPosition::Above),
+                Some(BandPosition::Below),
+            ]
+        );
+    }
+
+    #[test]
+    fn holds_back_changes_within_minimum_interval() {
+        let mut feedback = feedback(Duration::from_millis(100));
+        assert_eq!(
+            feedback.update(5.0, Instant::from_millis(0)),
+            Some(BandPosition::Below)
+        );
+        assert_eq!(feedback.update(15.0, Instant::from_millis(50)), None);
+        assert_eq!(
+            feedback.update(15.0, Instant::from_millis(100)),
+            Some(BandPosition::In)
+        );
+    }
+
+    #[test]
+    fn reset_sends_current_position_again() {
+        let mut feedback = feedback(Duration::from_secs(1));
+        feedback.update(15.0, Instant::from_millis(0));
+        feedback.reset();
+        assert_eq!(
+            feedback.update(15.0, Instant::from_millis(10)),
+            Some(BandPosition::In)
+        );
+    }
+
+    #[test]
+    fn disabled_sends_weight_and_no_events() {
+        let mut feedback = TargetFeedback::new(TargetBandConfig::DISABLED);
+        assert_eq!(feedback.update(15.0, Instant::from_millis(0)), None);
+        assert!(feedback.streams_weight());
+    }
+
+    #[test]
+    fn events_only_stops_weight_stream() {
+        let feedback = TargetFeedback::new(TargetBandConfig {
+            events_only: true,
+            ..feedback(Duration::from_ticks(0)).config()
+        });
+        assert!(!feedback.streams_weight());
+    }
+}
@@ -0,0 +1,273 @@
+/// Measurement filters
+///
+/// Filter stage applied to calibrated samples before they are sent to the client:
+/// median-of-N spike rejection, followed by a moving average and a first-order IIR
+/// low-pass. The filters are plain computations without hardware dependencies.
+use defmt::Format;
+
+/// Maximum window size of the median and moving average filters
+pub const MAX_FILTER_WINDOW: usize = 15;
+
+/// Filter pipeline configuration
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct FilterConfig {
+    /// Median window for spike rejection, 1 disables it
+    pub median_window: u8,
+    /// Moving average window, 1 disables it
+    pub average_window: u8,
+    /// Low-pass smoothing factor in (0, 1], 1 disables it
+    pub low_pass_alpha: f32,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl FilterConfig {
+    /// Configuration passing samples through unchanged
+    pub const DISABLED: Self = Self {
+        median_window: 1,
+        average_window: 1,
+        low_pass_alpha: 1.0,
+    };
+
+    /// Size of the serialized configuration in bytes
+    pub const SIZE: usize = 6;
+
+    /// Check if the configuration is within the supported ranges
+    pub fn is_valid(&self) -> bool {
+        let valid_window = |window: u8| (1..=MAX_FILTER_WINDOW).contains(&(window as usize));
+        valid_window(self.median_window)
+            && valid_window(self.average_window)
+            && self.low_pass_alpha > 0.0
+            && self.low_pass_alpha <= 1.0
+    }
+
+    /// Serialize as median window, average window and little-endian low-pass factor
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = self.median_window;
+        bytes[1] = self.average_window;
+        bytes[2..6].copy_from_slice(&self.low_pass_alpha.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a configuration, returning None if it is invalid
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let config = Self {
+            median_window: *bytes.first()?,
+            average_window: *bytes.get(1)?,
+            low_pass_alpha: f32::from_le_bytes(bytes.get(2..6)?.try_into().ok()?),
+        };
+        config.is_valid().then_some(config)
+    }
+}
+
+/// Fixed capacity window over the most recent samples
+#[derive(Clone, Copy, Debug)]
+struct Window {
+    samples: [f32; MAX_FILTER_WINDOW],
+    size: usize,
+    len: usize,
+    next: usize,
+}
+
+impl Window {
+    fn new(size: u8) -> Self {
+        Self {
+            samples: [0.0; MAX_FILTER_WINDOW],
+            size: (size as usize).clamp(1, MAX_FILTER_WINDOW),
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Add a sample, replacing the oldest one once the window is full
+    fn push(&mut self, sample: f32) -> &[f32] {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % self.size;
+        self.len = (self.len + 1).min(self.size);
+        &self.samples[..self.len]
+    }
+}
+
+/// Filter pipeline state
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    config: FilterConfig,
+    median: Window,
+    average: Window,
+    low_pass: Option<f32>,
+}
+
+impl Filter {
+    /// Create a filter pipeline with the given configuration
+    pub fn new(config: FilterConfig) -> Self {
+        Self {
+            config,
+            median: Window::new(config.median_window),
+            average: Window::new(config.average_window),
+            low_pass: None,
+        }
+    }
+
+    /// Get the filter configuration
+    pub fn config(&self) -> FilterConfig {
+        self.config
+    }
+
+    /// Discard the filter history, e.g. when a new measurement starts
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config);
+    }
+
+    /// Run a sample through the pipeline and return the filtered value
+    pub fn apply(&mut self, sample: f32) -> f32 {
+        let despiked = median(self.median.push(sample));
+        let averaged = mean(self.average.push(despiked));
+        let filtered = match self.low_pass {
+            Some(previous) => low_pass(previous, averaged, self.config.low_pass_alpha),
+            None => averaged,
+        };
+        self.low_pass = Some(filtered);
+        filtered
+    }
+}
+
+/// Arithmetic mean of the samples, 0 when empty
+pub fn mean(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().sum::<f32>() / samples.len() as f32
+}
+
+/// Median of the samples, averaging the two middle values for even lengths, 0 when empty
+pub fn median(samples: &[f32]) -> f32 {
+    let mut sorted = [0.0; MAX_FILTER_WINDOW];
+    let len = samples.len().min(MAX_FILTER_WINDOW);
+    if len == 0 {
+        return 0.0;
+    }
+
+    let sorted = &mut sorted[..len];
+    sorted.copy_from_slice(&samples[..len]);
+    sorted.sort_unstable_by(f32::total_cmp);
+
+    if len.is_multiple_of(2) {
+        (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0
+    } else {
+        sorted[len / 2]
+    }
+}
+
+/// First-order IIR low-pass: moves `previous` towards `sample` by a fraction `alpha`
+pub fn low_pass(previous: f32, sample: f32, alpha: f32) -> f32 {
+    previous + alpha * (sample - previous)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(median_window: u8, average_window: u8, low_pass_alpha: f32) -> Filter {
+        Filter::new(FilterConfig {
+            median_window,
+            average_window,
+            low_pass_alpha,
+        })
+    }
+
+    #[test]
+    fn median_rejects_single_spike() {
+        let mut filter = filter(3, 1, 1.0);
+        let output: [f32; 5] = [10.0, 10.0, 90.0, 10.0, 10.0].map(|sample| filter.apply(sample));
+        assert_eq!(output, [10.0; 5]);
+    }
+
+    #[test]
+    fn median_averages_middle_values_for_even_lengths() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+
+    #[test]
+    fn moving_average_warms_up_over_partial_window() {
+        let mut filter = filter(1, 4, 1.0);
+        let output = [4.0, 8.0, 12.0, 16.0, 20.0].map(|sample| filter.apply(sample));
+        assert_eq!(output, [4.0, 6.0, 8.0, 10.0, 14.0]);
+    }
+
+    #[test]
+    fn reset_discards_history() {
+        let mut filter = filter(1, 4, 0.5);
+        filter.apply(100.0);
+        filter.apply(100.0);
+        filter.reset();
+        assert_eq!(filter.apply(2.0), 2.0);
+    }
+
+    #[test]
+    fn mean_of_empty_is_zero() {
+        assert_eq!(mean(&[]), 0.0);
+        assert_eq!(mean(&[1.0, 2.0, 6.0]), 3.0);
+    }
+
+    #[test]
+    fn low_pass_alpha_edges() {
+        assert_eq!(low_pass(2.0, 10.0, 0.0), 2.0);
+        assert_eq!(low_pass(2.0, 10.0, 1.0), 10.0);
+        assert_eq!(low_pass(2.0, 10.0, 0.25), 4.0);
+    }
+
+    #[test]
+    fn low_pass_alpha_one_passes_samples_through() {
+        let mut filter = filter(1, 1, 1.0);
+        let output = [1.0, 5.0, -3.0].map(|sample| filter.apply(sample));
+        assert_eq!(output, [1.0, 5.0, -3.0]);
+    }
+
+    #[test]
+    fn low_pass_starts_from_first_sample() {
+        let mut filter = filter(1, 1, 0.5);
+        let output = [8.0, 0.0, 0.0].map(|sample| filter.apply(sample));
+        assert_eq!(output, [8.0, 4.0, 2.0]);
+    }
+
+    #[test]
+    fn config_round_trips_through_bytes() {
+        let config = FilterConfig {
+            median_window: 5,
+            average_window: 15,
+            low_pass_alpha: 0.2,
+        };
+        assert_eq!(FilterConfig::from_bytes(&config.to_bytes()), Some(config));
+        assert_eq!(
+            FilterConfig::from_bytes(&FilterConfig::DISABLED.to_bytes()),
+            Some(FilterConfig::DISABLED)
+        );
+    }
+
+    #[test]
+    fn config_from_bytes_rejects_invalid() {
+        let bytes = |median_window: u8, average_window: u8, low_pass_alpha: f32| {
+            FilterConfig {
+                median_window,
+                average_window,
+                low_pass_alpha,
+            }
+            .to_bytes()
+        };
+        assert_eq!(FilterConfig::from_bytes(&bytes(0, 1, 1.0)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(1, 0, 1.0)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(16, 1, 1.0)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(1, 16, 1.0)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(1, 1, 0.0)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(1, 1, 1.5)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(1, 1, f32::NAN)), None);
+        assert_eq!(FilterConfig::from_bytes(&bytes(1, 1, 1.0)[..5]), None);
+    }
+}
@@ -8,14 +8,18 @@
 /// [loadcell]: https://crates.io/crates/loadcell
 use core::fmt;
 
-use defmt::{debug, error, info};
+use defmt::{Format, debug, error, info, warn};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
 use embedded_hal::delay::DelayNs;
-use embedded_storage::{ReadStorage, Storage};
 use esp_hal::{
     delay::Delay,
     gpio::{Input, Output},
 };
-use esp_storage::FlashStorage;
+
+use crate::{
+    calibration::{self, CalibrationFit, CalibrationModel, CalibrationPoint},
+    storage::{self, CALIBRATION_FACTOR_ADDR, StorageError},
+};
 
 /// The absolute minimum readings. A smaller value should be clamped.
 const HX711_MINIMUM: i32 = -(2i32.saturating_pow(24 - 1));
@@ -23,27 +27,61 @@ const HX711_MINIMUM: i32 = -(2i32.saturating_pow(24 - 1));
 const HX711_MAXIMUM: i32 = 2i32.saturating_pow(24 - 1) - 1;
 /// The default delay time in microseconds for the HX711.
 const HX711_DELAY_TIME_US: u32 = 1;
+/// The time the clock is held high to enter power-down mode (more than 60 µs).
+const HX711_POWER_DOWN_TIME_US: u32 = 100;
+/// The output settling time after power-up at 80 SPS.
+const HX711_SETTLE_TIME: Duration = Duration::from_millis(50);
 /// The number of bits in the HX711 reading
 const HX711_DATA_BITS: usize = 24;
 /// The sign bit position in the HX711 reading
 const HX711_SIGN_BIT: u32 = 0x800000;
+/// Frame read when the data line stays low, e.g. after a bit-bang glitch. Also a
+/// legitimate reading of 0, so it is only discarded when it looks glitched.
+const HX711_FRAME_ZEROS: u32 = 0x000000;
+/// Frame read when the data line stays high, e.g. while the HX711 is powered down. Also
+/// a legitimate reading of -1, so it is only discarded when it looks glitched.
+const HX711_FRAME_ONES: u32 = 0xFFFFFF;
+/// The maximum number of extra reads used to replace a glitched or implausible sample.
+const MAX_READ_RETRIES: usize = 3;
+/// The largest plausible change between two consecutive samples, in kg.
+const MAX_SAMPLE_JUMP: f32 = 50.0;
+/// The maximum time to wait for a conversion before the HX711 is considered not responding.
+const HX711_READY_TIMEOUT: Duration = Duration::from_millis(500);
+/// Consecutive saturated readings before the sensor is reported as saturated (1 s at 80 Hz).
+const SATURATED_SAMPLES: usize = 80;
+/// Consecutive identical readings before the sensor is reported as stuck (1 s at 80 Hz).
+const STUCK_SAMPLES: usize = 80;
 
-/// The default address of the NVS flash storage.
-const NVS_ADDR: u32 = 0x9000;
 /// The default number of samples for taring
 const DEFAULT_TARING_SAMPLES: usize = 16;
+/// The default maximum noise (standard deviation) accepted for a tare, in kg.
+pub const DEFAULT_TARE_TOLERANCE: f32 = 0.02;
+/// The default time to wait for stable readings before a tare fails.
+const DEFAULT_TARE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Fraction of the remaining zero error corrected on each unloaded sample while auto-zeroing.
+const AUTO_ZERO_RATE: f32 = 0.005;
 /// The default number of samples for calibration
 const DEFAULT_CALIBRATION_SAMPLES: usize = 100;
-/// The default calibration value.
+/// The default number of samples for a calibration check (3 s at 80 Hz)
+const DEFAULT_VERIFICATION_SAMPLES: usize = 240;
+/// The default calibration value, used when no factory calibration was saved.
 const DEFAULT_CALIBRATION_FACTOR: f32 = 0.0639;
 
 /// Custom error type for HX711 operations
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum Hx711Error {
     /// Flash storage error
     FlashError,
     /// Invalid calibration value
     InvalidCalibration,
+    /// Tare readings did not settle within the tolerance, with the noise of the last attempt in kg
+    UnstableTare(f32),
+    /// The HX711 did not signal a conversion within the timeout
+    NotResponding,
+    /// Every read returned a glitched frame
+    Glitched,
+    /// The factory calibration was already saved
+    FactoryCalibrationLocked,
 }
 
 impl fmt::Display for Hx711Error {
@@ -51,6 +89,14 @@ impl fmt::Display for Hx711Error {
         match self {
             Hx711Error::FlashError => write!(f, "Flash storage error"),
             Hx711Error::InvalidCalibration => write!(f, "Invalid calibration value"),
+            Hx711Error::UnstableTare(noise) => {
+                write!(f, "Tare readings unstable, noise {} kg", noise)
+            }
+            Hx711Error::NotResponding => write!(f, "HX711 not responding"),
+            Hx711Error::Glitched => write!(f, "HX711 readings glitched"),
+            Hx711Error::FactoryCalibrationLocked => {
+                write!(f, "Factory calibration already saved")
+            }
         }
     }
 }
@@ -69,6 +115,106 @@ pub enum GainMode {
     A64 = 3,
 }
 
+/// Automatic zero tracking configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoZeroConfig {
+    /// Readings within ±`dead_band` kg are considered unloaded
+    pub dead_band: f32,
+    /// Time the reading must stay unloaded before the zero starts tracking
+    pub hold_time: Duration,
+    /// Maximum distance the zero may move away from the last tare, in kg
+    pub max_correction: f32,
+}
+
+impl Default for AutoZeroConfig {
+    fn default() -> Self {
+        Self {
+            dead_band: 0.1,
+            hold_time: Duration::from_secs(10),
+            max_correction: 0.5,
+        }
+    }
+}
+
+/// Largest span drift accepted, as a fraction of the weight per °C. Over the -40 to
+/// 125 °C range of the chip sensor, the span correction stays within 1 ± 0.5.
+const MAX_SPAN_DRIFT: f32 = 0.003;
+
+/// Temperature compensation of the load cell drift
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct TemperatureCompensation {
+    /// Zero drift since the tare, in kg per °C
+    pub zero_drift: f32,
+    /// Span drift since the calibration, as a fraction of the weight per °C
+    pub span_drift: f32,
+}
+
+impl Default for TemperatureCompensation {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl TemperatureCompensation {
+    /// No compensation
+    pub const DISABLED: Self = Self {
+        zero_drift: 0.0,
+        span_drift: 0.0,
+    };
+
+    /// Size of the serialized compensation in bytes
+    pub const SIZE: usize = 8;
+
+    /// Check if the compensation is within the supported ranges
+    pub fn is_valid(&self) -> bool {
+        self.zero_drift.is_finite()
+            && self.span_drift.is_finite()
+            && self.span_drift.abs() <= MAX_SPAN_DRIFT
+    }
+
+    /// Serialize as little-endian zero and span drift
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.zero_drift.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.span_drift.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a compensation, returning None if it is invalid
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let compensation = Self {
+            zero_drift: f32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?),
+            span_drift: f32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?),
+        };
+        compensation.is_valid().then_some(compensation)
+    }
+}
+
+/// Counters of glitched, saturated and implausible readings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Format)]
+pub struct ReadDiagnostics {
+    /// Repeated all-zeros or all-ones frames, discarded and read again
+    pub glitches: u32,
+    /// Readings at the limits of the ADC range
+    pub saturated: u32,
+    /// Single-sample jumps discarded as implausible
+    pub spikes: u32,
+}
+
+/// Health of the load cell, derived from the latest readings
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub enum SensorHealth {
+    /// Readings look plausible
+    Ok = 0,
+    /// The HX711 does not signal conversions, e.g. unplugged or unpowered
+    NotResponding = 1,
+    /// Readings stay at the limits of the ADC range
+    Saturated = 2,
+    /// Readings stay at the exact same value
+    StuckValue = 3,
+}
+
 /// HX711 24-bit ADC driver
 pub struct Hx711<'d> {
     /// Data pin
@@ -77,24 +223,92 @@ pub struct Hx711<'d> {
     clock: Output<'d>,
     /// Delay instance
     delay: Delay,
-    /// Flash storage
-    flash: FlashStorage<'d>,
     /// Gain mode
     gain_mode: GainMode,
     /// Tare value
     tare_value: i32,
-    /// Calibration
-    calibration_factor: f32,
+    /// Calibration model
+    calibration_model: CalibrationModel,
+    /// Tare in progress, fed from the regular read path
+    pending_tare: Option<PendingTare>,
+    /// Result of the last finished tare, until taken
+    tare_result: Option<Result<f32, Hx711Error>>,
+    /// Automatic zero tracking, disabled when None
+    auto_zero: Option<AutoZeroConfig>,
+    /// Zero correction applied on top of the tare value by auto-zero, in raw counts
+    auto_zero_offset: f32,
+    /// Time since the calibrated reading has been within the auto-zero dead band
+    unloaded_since: Option<Instant>,
+    /// Last accepted raw reading, reference for the jump check
+    last_raw: Option<i32>,
+    /// Last frame read, to spot repeated all-zeros or all-ones frames
+    last_frame: Option<u32>,
+    /// Glitch and outlier counters
+    diagnostics: ReadDiagnostics,
+    /// Sensor health
+    health: SensorHealth,
+    /// Number of consecutive saturated readings
+    saturated_count: usize,
+    /// Number of consecutive identical readings
+    stuck_count: usize,
+    /// HX711 in power-down mode
+    powered_down: bool,
+    /// Load cell temperature in °C, if known
+    temperature: Option<f32>,
+    /// Temperature at the last tare
+    tare_temperature: Option<f32>,
+    /// Temperature when the calibration was applied
+    calibration_temperature: Option<f32>,
+    /// Temperature compensation
+    temperature_compensation: TemperatureCompensation,
+}
+
+/// Accumulator for a tare that runs alongside regular readings
+#[derive(Clone, Copy, Debug)]
+struct PendingTare {
+    /// Sum of the raw samples collected so far
+    total: i64,
+    /// Sum of the squared raw samples collected so far
+    total_sq: i64,
+    /// Number of raw samples collected so far
+    samples: usize,
+    /// Maximum noise accepted, in kg
+    tolerance: f32,
+    /// Time after which the tare gives up waiting for stable readings
+    deadline: Instant,
+}
+
+impl PendingTare {
+    fn new(tolerance: f32) -> Self {
+        Self {
+            total: 0,
+            total_sq: 0,
+            samples: 0,
+            tolerance,
+            deadline: Instant::now() + DEFAULT_TARE_TIMEOUT,
+        }
+    }
+
+    /// Discard the collected samples and start a new window.
+    fn restart(&mut self) {
+        self.total = 0;
+        self.total_sq = 0;
+        self.samples = 0;
+    }
+
+    /// Mean and variance of the collected raw samples.
+    fn statistics(&self) -> (i32, f32) {
+        let n = self.samples as i64;
+        let mean = self.total / n;
+        // n² · variance = n · Σx² − (Σx)², exact in integers for a tare window
+        let variance = (n * self.total_sq - self.total * self.total) as f32 / (n * n) as f32;
+        (mean as i32, variance)
+    }
 }
 
 impl<'d> Hx711<'d> {
     /// Create a new HX711 driver.
-    pub fn new(
-        data: Input<'d>,
-        mut clock: Output<'d>,
-        delay: Delay,
-        flash: FlashStorage<'d>,
-    ) -> Self {
+    pub fn new(data: Input<'d>, mut clock: Output<'d>, delay: Delay) -> Self {
         info!("HX711 initialized");
         clock.set_low();
 
@@ -102,15 +316,28 @@ impl<'d> Hx711<'d> {
             data,
             clock,
             delay,
-            flash,
             gain_mode: GainMode::A64,
             tare_value: 0,
-            calibration_factor: 0.0,
+            calibration_model: CalibrationModel::Linear(DEFAULT_CALIBRATION_FACTOR),
+            pending_tare: None,
+            tare_result: None,
+            auto_zero: None,
+            auto_zero_offset: 0.0,
+            unloaded_since: None,
+            last_raw: None,
+            last_frame: None,
+            diagnostics: ReadDiagnostics::default(),
+            health: SensorHealth::Ok,
+            saturated_count: 0,
+            stuck_count: 0,
+            powered_down: false,
+            temperature: None,
+            tare_temperature: None,
+            calibration_temperature: storage::read_calibration_temperature().ok(),
+            temperature_compensation: TemperatureCompensation::DISABLED,
         };
 
-        hx711.calibration_factor = hx711
-            .get_calibration_factor()
-            .unwrap_or(DEFAULT_CALIBRATION_FACTOR);
+        hx711.calibration_model = hx711.load_calibration_model();
 
         hx711
     }
@@ -119,7 +346,7 @@ impl<'d> Hx711<'d> {
     fn read_from_flash(&mut self) -> Result<f32, Hx711Error> {
         let mut bytes = [0u8; 4];
 
-        self.flash.read(NVS_ADDR, &mut bytes).map_err(|_| {
+        storage::read(CALIBRATION_FACTOR_ADDR, &mut bytes).map_err(|_| {
             error!("Failed to read calibration factor from flash");
             Hx711Error::FlashError
         })?;
@@ -139,33 +366,65 @@ impl<'d> Hx711<'d> {
         !factor.is_nan() && factor != 0.0
     }
 
-    /// Write calibration factor to flash
-    fn write_to_flash(&mut self, calibration_factor: f32) -> Result<(), Hx711Error> {
-        if !Self::is_valid_calibration_factor(calibration_factor) {
-            return Err(Hx711Error::InvalidCalibration);
+    /// Read the calibration model from flash, falling back to the calibration factor.
+    fn load_calibration_model(&mut self) -> CalibrationModel {
+        match storage::read_calibration_model() {
+            Ok(model) => {
+                info!("Calibration model read from flash: {:?}", model.kind());
+                model
+            }
+            Err(_) => match self.read_from_flash() {
+                Ok(factor) => CalibrationModel::Linear(factor),
+                Err(_) => Self::factory_calibration()
+                    .map(|(model, _)| model)
+                    .unwrap_or(CalibrationModel::Linear(DEFAULT_CALIBRATION_FACTOR)),
+            },
         }
+    }
 
-        let bytes = calibration_factor.to_le_bytes();
+    /// Read the factory calibration model and its temperature, if one was saved.
+    fn factory_calibration() -> Option<(CalibrationModel, Option<f32>)> {
+        storage::read_factory_calibration().ok()
+    }
 
-        self.flash.write(NVS_ADDR, &bytes).map_err(|_| {
-            error!("Failed to write calibration factor to flash");
-            Hx711Error::FlashError
+    /// Save the calibration in use as the factory calibration.
+    ///
+    /// The factory calibration is written once, during production; later attempts fail.
+    pub fn save_factory_calibration(&self) -> Result<(), Hx711Error> {
+        storage::write_factory_calibration(&self.calibration_model, self.calibration_temperature)
+            .map_err(|e| {
+            error!(
+                "Failed to write factory calibration: {:?}",
+                defmt::Debug2Format(&e)
+            );
+            match e {
+                StorageError::Locked => Hx711Error::FactoryCalibrationLocked,
+                _ => Hx711Error::FlashError,
+            }
         })?;
-
+        info!(
+            "Factory calibration saved: {:?}",
+            self.calibration_model.kind()
+        );
         Ok(())
     }
 
-    /// Update the calibration factor in memory and flash.
-    pub fn update_calibration_factor(&mut self, factor: f32) -> Result<(), Hx711Error> {
-        if !Self::is_valid_calibration_factor(factor) {
-            error!("Invalid calibration factor: {}", factor);
+    /// Update the calibration model in memory and flash.
+    ///
+    /// The scale factor around zero is stored as the calibration factor too, in the
+    /// same flash write.
+    pub fn update_calibration_model(&mut self, model: CalibrationModel) -> Result<(), Hx711Error> {
+        if !model.is_valid() || !Self::is_valid_calibration_factor(model.factor()) {
+            error!("Invalid calibration model: {:?}", model.kind());
             return Err(Hx711Error::InvalidCalibration);
         }
 
-        info!("Updating calibration factor: {}", factor);
-        self.write_to_flash(factor)?;
+        storage::write_calibration_model(&model).map_err(|_| {
+            error!("Failed to write calibration model to flash");
+            Hx711Error::FlashError
+        })?;
 
-        self.calibration_factor = factor;
+        self.calibration_model = model;
         Ok(())
     }
 
@@ -177,6 +436,10 @@ impl<'d> Hx711<'d> {
                 Ok(factor)
             }
             Err(Hx711Error::InvalidCalibration) => {
+                if let Some((model, _)) = Self::factory_calibration() {
+                    info!("Using factory calibration factor");
+                    return Ok(model.factor());
+                }
                 info!("Using default calibration factor");
                 Ok(DEFAULT_CALIBRATION_FACTOR)
             }
@@ -184,19 +447,80 @@ impl<'d> Hx711<'d> {
         }
     }
 
-    /// Get the current calibration factor.
+    /// Get the current calibration factor, the scale around zero.
     pub fn current_calibration_factor(&self) -> f32 {
-        self.calibration_factor
+        self.calibration_model.factor()
+    }
+
+    /// Get the calibration model.
+    pub fn calibration_model(&self) -> CalibrationModel {
+        self.calibration_model
     }
 
-    /// Set the default calibration factor.
+    /// Restore the factory calibration, or the default calibration factor if none was saved.
     pub fn default_calibration_factor(&mut self) -> Result<(), Hx711Error> {
+        if let Some((model, temperature)) = Self::factory_calibration() {
+            debug!("Restoring factory calibration");
+            self.update_calibration_model(model)?;
+            self.set_calibration_temperature(temperature);
+            return Ok(());
+        }
+
         debug!("Restoring default calibration factor");
-        self.write_to_flash(DEFAULT_CALIBRATION_FACTOR)?;
-        self.calibration_factor = DEFAULT_CALIBRATION_FACTOR;
+        self.update_calibration_model(CalibrationModel::Linear(DEFAULT_CALIBRATION_FACTOR))?;
+        // The default factor was not measured at any particular temperature
+        self.set_calibration_temperature(None);
         Ok(())
     }
 
+    /// Record and persist the temperature of the calibration in use.
+    fn set_calibration_temperature(&mut self, temperature: Option<f32>) {
+        info!("Calibration temperature: {:?}", temperature);
+        self.calibration_temperature = temperature;
+        if storage::write_calibration_temperature(temperature).is_err() {
+            error!("Failed to write calibration temperature to flash");
+        }
+    }
+
+    /// Updates the load cell temperature, from the internal or an external sensor.
+    pub fn set_temperature(&mut self, temperature: Option<f32>) {
+        self.temperature = temperature;
+    }
+
+    /// Get the temperature compensation.
+    pub fn temperature_compensation(&self) -> TemperatureCompensation {
+        self.temperature_compensation
+    }
+
+    /// Sets the temperature compensation applied to calibrated readings.
+    pub fn set_temperature_compensation(&mut self, compensation: TemperatureCompensation) {
+        self.temperature_compensation = compensation;
+    }
+
+    /// Corrects the zero drift since the tare and the span drift since the calibration.
+    ///
+    /// Corrections whose reference temperature is unknown are skipped.
+    fn compensate_temperature(&self, weight: f32) -> f32 {
+        let Some(temperature) = self.temperature else {
+            return weight;
+        };
+        let compensation = self.temperature_compensation;
+
+        let mut weight = weight;
+        if let Some(tare_temperature) = self.tare_temperature {
+            weight -= compensation.zero_drift * (temperature - tare_temperature);
+        }
+        if let Some(calibration_temperature) = self.calibration_temperature {
+            weight /= 1.0 + compensation.span_drift * (temperature - calibration_temperature);
+        }
+        weight
+    }
+
+    /// Raw value of the current zero: tare value and auto-zero correction.
+    fn zero(&self) -> i32 {
+        self.tare_value + self.auto_zero_offset as i32
+    }
+
     /// Reads a single bit from the data pin.
     #[inline]
     fn read_data_bit(&mut self) -> bool {
@@ -234,9 +558,9 @@ impl<'d> Hx711<'d> {
         self.gain_mode
     }
 
-    /// Reads 24 bits from the HX711 within a critical section.
-    fn read_raw(&mut self) -> i32 {
-        let value = critical_section::with(|_| {
+    /// Reads a 24 bits frame from the HX711 within a critical section.
+    fn read_frame(&mut self) -> u32 {
+        let frame = critical_section::with(|_| {
             let mut result: u32 = 0;
             for _ in 0..HX711_DATA_BITS {
                 result = (result << 1) | (self.read_data_bit() as u32);
@@ -246,154 +570,396 @@ impl<'d> Hx711<'d> {
 
         self.send_gain_pulses();
 
+        frame
+    }
+
+    /// Converts a 24 bits frame into a signed reading.
+    fn decode(frame: u32) -> i32 {
         // Handle sign extension for 24-bit signed values
-        let extended_value = if value & HX711_SIGN_BIT != 0 {
-            value | 0xFF000000 // Negative value, extend the sign bit
+        let extended_value = if frame & HX711_SIGN_BIT != 0 {
+            frame | 0xFF000000 // Negative value, extend the sign bit
         } else {
-            value // Positive value, no change
+            frame // Positive value, no change
         };
 
         // Clamp to valid range and return as signed 32-bit
         (extended_value as i32).clamp(HX711_MINIMUM, HX711_MAXIMUM)
     }
 
+    /// Reads a raw value, discarding glitched frames and implausible jumps.
+    ///
+    /// All-zeros and all-ones frames are valid readings of 0 and -1, but also what a stuck
+    /// data line or a glitched bit-bang reads. A single one is accepted as a reading, as
+    /// a well-tared cell reads zero near zero load, but one repeating the previous frame
+    /// is read again: a real reading near zero is noisy enough not to repeat exactly.
+    ///
+    /// A jump larger than [`MAX_SAMPLE_JUMP`] from the last accepted reading is only
+    /// accepted once a second reading confirms it, otherwise it is discarded as a spike.
+    /// After [`MAX_READ_RETRIES`] extra reads the latest reading is returned as is, or
+    /// [`Hx711Error::Glitched`] if every frame was discarded.
+    ///
+    /// Powers the HX711 up if needed.
+    async fn read_raw(&mut self) -> Result<i32, Hx711Error> {
+        self.power_up().await?;
+
+        let max_jump = (MAX_SAMPLE_JUMP * 1000.0 / self.current_calibration_factor().abs()) as u32;
+        let mut suspect: Option<i32> = None;
+        let mut reading: Option<i32> = None;
+
+        for _ in 0..=MAX_READ_RETRIES {
+            if let Err(e) = self.wait_for_ready().await {
+                self.health = SensorHealth::NotResponding;
+                return Err(e);
+            }
+            let frame = self.read_frame();
+            let repeated = self.last_frame.replace(frame) == Some(frame);
+            if (frame == HX711_FRAME_ZEROS || frame == HX711_FRAME_ONES) && repeated {
+                self.diagnostics.glitches += 1;
+                debug!("Discarding glitched HX711 frame: {:#x}", frame);
+                continue;
+            }
+
+            let raw = Self::decode(frame);
+            reading = Some(raw);
+            if raw == HX711_MINIMUM || raw == HX711_MAXIMUM {
+                self.diagnostics.saturated += 1;
+            }
+
+            let Some(last) = self.last_raw else {
+                break;
+            };
+            if raw.abs_diff(last) <= max_jump {
+                if suspect.is_some() {
+                    self.diagnostics.spikes += 1;
+                    debug!("Discarding spike: {} after {}", suspect, last);
+                }
+                break;
+            }
+            if let Some(previous) = suspect
+                && raw.abs_diff(previous) <= max_jump
+            {
+                // Two consecutive readings agree, this is a real step
+                break;
+            }
+            suspect = Some(raw);
+        }
+
+        // A stale reading would pass for a fresh one, and count towards a stuck value
+        let raw = reading.ok_or(Hx711Error::Glitched)?;
+        self.update_health(raw);
+        self.last_raw = Some(raw);
+        Ok(raw)
+    }
+
+    /// Updates the sensor health with a new accepted reading.
+    fn update_health(&mut self, raw: i32) {
+        if raw == HX711_MINIMUM || raw == HX711_MAXIMUM {
+            self.saturated_count += 1;
+        } else {
+            self.saturated_count = 0;
+        }
+
+        if self.last_raw == Some(raw) {
+            self.stuck_count += 1;
+        } else {
+            self.stuck_count = 0;
+        }
+
+        self.health = if self.saturated_count >= SATURATED_SAMPLES {
+            SensorHealth::Saturated
+        } else if self.stuck_count >= STUCK_SAMPLES {
+            SensorHealth::StuckValue
+        } else {
+            SensorHealth::Ok
+        };
+    }
+
+    /// Gets the sensor health.
+    pub fn health(&self) -> SensorHealth {
+        self.health
+    }
+
+    /// Gets the glitch and outlier counters.
+    pub fn diagnostics(&self) -> ReadDiagnostics {
+        self.diagnostics
+    }
+
+    /// Puts the HX711 in power-down mode, holding the clock high for more than 60 µs.
+    ///
+    /// The next reading powers it up again.
+    pub fn power_down(&mut self) {
+        if self.powered_down {
+            return;
+        }
+
+        debug!("Powering down the HX711");
+        self.clock.set_high();
+        self.delay.delay_us(HX711_POWER_DOWN_TIME_US);
+        self.powered_down = true;
+    }
+
+    /// Wakes the HX711 from power-down mode and waits for the output to settle.
+    pub async fn power_up(&mut self) -> Result<(), Hx711Error> {
+        if !self.powered_down {
+            return Ok(());
+        }
+
+        debug!("Powering up the HX711");
+        self.clock.set_low();
+        self.powered_down = false;
+        // The load may have changed while powered down
+        self.last_raw = None;
+        self.last_frame = None;
+        Timer::after(HX711_SETTLE_TIME).await;
+
+        // After power-up the HX711 converts on channel A with a gain of 128. Discard that
+        // conversion, reading it sends the pulses selecting the configured gain.
+        if let Err(e) = self.wait_for_ready().await {
+            self.health = SensorHealth::NotResponding;
+            return Err(e);
+        }
+        self.read_frame();
+        Ok(())
+    }
+
+    /// Returns true while the HX711 is in power-down mode.
+    pub fn is_powered_down(&self) -> bool {
+        self.powered_down
+    }
+
     /// Waits until the data is ready to be read.
-    async fn wait_for_ready(&mut self) {
-        self.data.wait_for_low().await;
+    async fn wait_for_ready(&mut self) -> Result<(), Hx711Error> {
+        with_timeout(HX711_READY_TIMEOUT, self.data.wait_for_low())
+            .await
+            .map_err(|_| Hx711Error::NotResponding)
     }
 
-    /// Takes multiple samples and returns the average
-    async fn take_samples(&mut self, num_samples: usize) -> f32 {
+    /// Waits for the next sample and feeds it to the pending tare, if any.
+    ///
+    /// A pending tare is aborted if the HX711 stops responding, glitched reads are skipped.
+    async fn next_sample(&mut self) -> Result<i32, Hx711Error> {
+        let raw = match self.read_raw().await {
+            Ok(raw) => raw,
+            Err(e) => {
+                if matches!(e, Hx711Error::NotResponding) && self.pending_tare.take().is_some() {
+                    self.tare_result = Some(Err(e));
+                }
+                return Err(e);
+            }
+        };
+
+        let factor = self.current_calibration_factor().abs();
+        if let Some(pending) = self.pending_tare.as_mut() {
+            pending.total += raw as i64;
+            pending.total_sq += raw as i64 * raw as i64;
+            pending.samples += 1;
+            if pending.samples >= DEFAULT_TARING_SAMPLES {
+                let (mean, variance) = pending.statistics();
+                let noise = sqrt(variance) * factor / 1000.0;
+
+                if noise <= pending.tolerance {
+                    self.tare_value = mean;
+                    self.tare_temperature = self.temperature;
+                    self.auto_zero_offset = 0.0;
+                    self.unloaded_since = None;
+                    self.pending_tare = None;
+                    self.tare_result = Some(Ok(noise));
+                    debug!(
+                        "Tare value set to: {} (noise {} kg)",
+                        self.tare_value, noise
+                    );
+                } else if Instant::now() >= pending.deadline {
+                    self.pending_tare = None;
+                    self.tare_result = Some(Err(Hx711Error::UnstableTare(noise)));
+                    warn!("Tare timed out, readings unstable (noise {} kg)", noise);
+                } else {
+                    debug!("Tare readings unstable (noise {} kg), retrying", noise);
+                    pending.restart();
+                }
+            }
+        }
+
+        Ok(raw)
+    }
+
+    /// Takes multiple tared samples and returns the average
+    async fn take_samples(&mut self, num_samples: usize) -> Result<f32, Hx711Error> {
         let mut total: f32 = 0.0;
 
         for _ in 0..num_samples {
-            self.wait_for_ready().await;
-            total += self.read_raw() as f32;
+            total += self.read_tared().await? as f32;
         }
 
-        total / num_samples as f32
+        Ok(total / num_samples as f32)
     }
 
-    /// Tares the sensor by measuring the average of several readings.
-    pub async fn tare(&mut self) {
+    /// Starts taring the sensor without blocking.
+    ///
+    /// The tare value is updated once [`DEFAULT_TARING_SAMPLES`] consecutive readings,
+    /// collected through the regular read methods, have a noise (standard deviation)
+    /// within `tolerance` kg. Noisy windows are discarded and collected again until
+    /// [`DEFAULT_TARE_TIMEOUT`] expires, in which case the previous tare value is kept.
+    pub fn start_tare(&mut self, tolerance: f32) -> Result<(), Hx711Error> {
         debug!("Taring the scale");
-        if !Self::is_valid_calibration_factor(self.calibration_factor) {
+        if !Self::is_valid_calibration_factor(self.current_calibration_factor()) {
             info!("Invalid calibration factor, skipping tare");
-            return;
+            return Err(Hx711Error::InvalidCalibration);
         }
 
-        let average = self.take_samples(DEFAULT_TARING_SAMPLES).await;
-        self.tare_value = average as i32;
-        debug!("Tare value set to: {}", self.tare_value);
+        self.pending_tare = Some(PendingTare::new(tolerance));
+        self.tare_result = None;
+        Ok(())
+    }
+
+    /// Returns true while a tare is collecting samples.
+    pub fn is_taring(&self) -> bool {
+        self.pending_tare.is_some()
+    }
+
+    /// Takes the result of the last finished tare: the achieved noise in kg, or
+    /// [`Hx711Error::UnstableTare`] if the readings never settled.
+    pub fn take_tare_result(&mut self) -> Option<Result<f32, Hx711Error>> {
+        self.tare_result.take()
+    }
+
+    /// Tares the sensor by measuring the average of several stable readings.
+    ///
+    /// Returns the achieved noise in kg.
+    pub async fn tare(&mut self, tolerance: f32) -> Result<f32, Hx711Error> {
+        self.start_tare(tolerance)?;
+        while self.is_taring() {
+            // Errors abort the tare and are reported as its result
+            let _ = self.next_sample().await;
+        }
+        self.take_tare_result()
+            .unwrap_or(Err(Hx711Error::InvalidCalibration))
     }
 
     /// Reads a raw value without calibration
-    pub async fn read_raw_value(&mut self) -> i32 {
-        self.wait_for_ready().await;
-        self.read_raw()
+    pub async fn read_raw_value(&mut self) -> Result<i32, Hx711Error> {
+        self.next_sample().await
     }
 
-    /// Reads a tared raw value (raw value minus tare value)
-    pub async fn read_tared(&mut self) -> i32 {
-        self.wait_for_ready().await;
-        self.read_raw() - self.tare_value
+    /// Reads a tared raw value (raw value minus tare value and auto-zero correction)
+    pub async fn read_tared(&mut self) -> Result<i32, Hx711Error> {
+        Ok(self.next_sample().await? - self.zero())
     }
 
     /// Reads a calibrated value, in kg.
-    pub async fn read_calibrated(&mut self) -> f32 {
-        let raw_tared = self.read_tared().await;
-        let calibrated_value = (raw_tared as f32) * self.calibration_factor;
-        // Convert to kg
-        calibrated_value / 1000.0
+    pub async fn read_calibrated(&mut self) -> Result<f32, Hx711Error> {
+        let raw_tared = self.read_tared().await?;
+        let weight = self.compensate_temperature(self.calibration_model.weight(raw_tared as f32));
+        self.track_zero(raw_tared, weight);
+        Ok(weight)
     }
 
-    /// Perform two-point calibration with a known target weight
-    ///
-    /// This method collects raw values for calibration by taking multiple samples
-    /// and averaging them for stability.
-    ///
-    /// Returns the average raw value for the calibration point.
-    pub async fn perform_calibration(&mut self) -> f32 {
-        // Reset calibration to raw values first
-        if let Err(e) = self.update_calibration_factor(1.0) {
-            error!(
-                "Failed to reset calibration factor before calibration: {:?}",
-                defmt::Debug2Format(&e)
+    /// Enables or disables automatic zero tracking.
+    pub fn set_auto_zero(&mut self, config: Option<AutoZeroConfig>) {
+        if self.auto_zero != config {
+            info!(
+                "Auto-zero {}",
+                if config.is_some() {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
             );
+            self.auto_zero = config;
+            self.unloaded_since = None;
         }
+    }
+
+    /// Slowly moves the zero towards unloaded readings to compensate drift.
+    ///
+    /// Tracking only starts once the reading has stayed within the dead band for the
+    /// configured hold time, so it never acts during a pull or a tare.
+    fn track_zero(&mut self, raw_tared: i32, weight: f32) {
+        let Some(config) = self.auto_zero else {
+            return;
+        };
+
+        if self.is_taring() || weight.abs() > config.dead_band {
+            self.unloaded_since = None;
+            return;
+        }
+
+        let now = Instant::now();
+        let unloaded_since = *self.unloaded_since.get_or_insert(now);
+        if now - unloaded_since < config.hold_time {
+            return;
+        }
+
+        let limit = config.max_correction * 1000.0 / self.current_calibration_factor().abs();
+        self.auto_zero_offset =
+            (self.auto_zero_offset + raw_tared as f32 * AUTO_ZERO_RATE).clamp(-limit, limit);
+    }
+
+    /// Collect a calibration point for a known target weight
+    ///
+    /// This method collects tared raw values by taking multiple samples and averaging
+    /// them for stability. The calibration in use is left untouched.
+    ///
+    /// Returns the average tared raw value for the calibration point.
+    pub async fn perform_calibration(&mut self) -> Result<f32, Hx711Error> {
         // Take multiple readings and average them for stability
-        let average_value = self.take_samples(DEFAULT_CALIBRATION_SAMPLES).await;
+        let average_value = self.take_samples(DEFAULT_CALIBRATION_SAMPLES).await?;
         debug!("Calibration point collected: {}", average_value);
 
-        average_value
+        Ok(average_value)
     }
 
-    /// Apply multi-point calibration using the collected calibration points.
+    /// Measure a known weight to check the calibration
     ///
-    /// This method calculates and applies a best-fit calibration factor
-    /// based on the provided (raw_value, weight) pairs.
+    /// Averages calibrated readings for a few seconds, without changing the calibration.
     ///
-    /// Returns true if calibration was successfully applied, false otherwise.
-    pub fn apply_multi_point_calibration(&mut self, calibration_points: &[(f32, f32)]) -> bool {
-        if calibration_points.len() < 2 {
-            error!("Calibration requires at least two points");
-            return false;
+    /// Returns the average weight in kg.
+    pub async fn measure_weight(&mut self) -> Result<f32, Hx711Error> {
+        let mut total: f32 = 0.0;
+        for _ in 0..DEFAULT_VERIFICATION_SAMPLES {
+            total += self.read_calibrated().await?;
         }
+        let average_value = total / DEFAULT_VERIFICATION_SAMPLES as f32;
+        debug!("Verification weight measured: {}", average_value);
 
-        let mut valid_count = 0usize;
-        let mut base_point: Option<(f32, f32)> = None;
-        let mut sum_delta_raw_weight = 0.0;
-        let mut sum_delta_raw_sq = 0.0;
-
-        for (raw_value, weight) in calibration_points {
-            if !raw_value.is_finite() || !weight.is_finite() || *weight < 0.0 {
-                error!(
-                    "Skipping invalid calibration point raw={}, weight={}",
-                    raw_value, weight
-                );
-                continue;
-            }
+        Ok(average_value)
+    }
 
-            valid_count += 1;
-            if let Some((base_raw, base_weight)) = base_point {
-                let delta_raw = raw_value - base_raw;
-                // Incoming calibration weights are expressed in kg, while
-                // calibration_factor operates on grams before read_calibrated()
-                // converts back to kg.
-                let delta_weight = (weight - base_weight) * 1000.0;
-                sum_delta_raw_weight += delta_raw * delta_weight;
-                sum_delta_raw_sq += delta_raw * delta_raw;
-            } else {
-                base_point = Some((*raw_value, *weight));
-            }
-        }
+    /// Apply multi-point calibration using the collected calibration points.
+    ///
+    /// Fits the calibration model supported by the number of (raw_value, weight)
+    /// points, see [`calibration::fit`], and applies it.
+    ///
+    /// Returns the fit, with the residual of each point.
+    pub fn apply_multi_point_calibration(
+        &mut self,
+        calibration_points: &[CalibrationPoint],
+    ) -> Result<CalibrationFit, Hx711Error> {
+        let fit = calibration::fit(calibration_points).map_err(|e| {
+            error!("Invalid calibration: {:?}", e);
+            Hx711Error::InvalidCalibration
+        })?;
 
-        if valid_count < 2 {
-            error!("Calibration requires at least two valid points");
-            return false;
-        }
+        self.update_calibration_model(fit.model)?;
+        self.set_calibration_temperature(self.temperature);
+        info!(
+            "Calibration model successfully applied: {:?}, factor: {:?}",
+            fit.model.kind(),
+            self.current_calibration_factor()
+        );
+        Ok(fit)
+    }
+}
 
-        if sum_delta_raw_sq.abs() < f32::EPSILON {
-            error!("Invalid calibration - points are too close together");
-            return false;
-        }
+/// Square root using Newton's method, as `f32::sqrt` is not available in `core`.
+fn sqrt(value: f32) -> f32 {
+    if value <= 0.0 || !value.is_finite() {
+        return value.max(0.0);
+    }
 
-        let scale_factor = sum_delta_raw_weight / sum_delta_raw_sq;
-        match self.update_calibration_factor(scale_factor) {
-            Ok(_) => {
-                info!(
-                    "Calibration factor successfully applied: {:?}",
-                    scale_factor
-                );
-                true
-            }
-            Err(e) => {
-                error!(
-                    "Failed to apply calibration factor: {:?}",
-                    defmt::Debug2Format(&e)
-                );
-                false
-            }
-        }
+    // Halving the exponent bits gives a close first guess
+    let mut root = f32::from_bits(0x1FBD_1DF5 + (value.to_bits() >> 1));
+    for _ in 0..3 {
+        root = 0.5 * (root + value / root);
     }
+    root
 }
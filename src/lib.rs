@@ -0,0 +1,34 @@
+//! Hardware independent parts of the firmware
+//!
+//! Built for the firmware and for the host, where their tests run with
+//! `cargo +stable test --lib --target x86_64-unknown-linux-gnu`. The nightly toolchain
+//! of the firmware builds `core` from source, which the host tests cannot link with.
+#![no_std]
+
+pub mod calibration;
+pub mod critical_force;
+pub mod feedback;
+pub mod filter;
+pub mod reps;
+pub mod transfer;
+pub mod trigger;
+pub mod workout;
+
+/// defmt logger of the host tests, the firmware one sends over RTT
+#[cfg(test)]
+mod test_logger {
+    #[defmt::global_logger]
+    struct Logger;
+
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(_bytes: &[u8]) {}
+    }
+
+    #[defmt::panic_handler]
+    fn panic() -> ! {
+        core::panic!("defmt panic")
+    }
+}
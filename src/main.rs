@@ -7,21 +7,22 @@ use bt_hci::controller::ExternalController;
 use critical_section::Mutex;
 use defmt::{debug, error, info, warn};
 use embassy_executor::Spawner;
-use embassy_futures::{join::join, select::select};
+use embassy_futures::{join::join, select::select3};
 use embassy_sync::channel::Channel;
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
 use esp_hal::{
     Async,
     Config,
     analog::adc::{Adc, AdcCalCurve, AdcConfig, AdcPin, Attenuation},
     clock::CpuClock,
     delay::Delay,
-    gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull},
+    gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull, RtcPin},
     interrupt::software::SoftwareInterruptControl,
     peripherals,
     rtc_cntl::Rtc,
     time,
     timer::timg::TimerGroup,
+    tsens::{self, TemperatureSensor},
 };
 use esp_radio::ble::controller::BleConnector;
 use esp_storage::FlashStorage;
@@ -31,22 +32,58 @@ use trouble_host::prelude::*;
 
 use crate::{
     ble::{CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU, Server, advertise},
-    hx711::Hx711,
-    progressor::{
+    calibration::{
+        CalibrationFit,
         CalibrationPoint,
+        MAX_CALIBRATION_POINTS,
+        MIN_CALIBRATION_POINTS,
+    },
+    critical_force::{CriticalForceTest, TestEvent},
+    feedback::{TargetBandConfig, TargetFeedback},
+    filter::{Filter, FilterConfig},
+    hx711::{
+        DEFAULT_TARE_TOLERANCE,
+        Hx711,
+        Hx711Error,
+        ReadDiagnostics,
+        SensorHealth,
+        TemperatureCompensation,
+    },
+    progressor::{
         ControlOpCode,
         DataPoint,
         DataPointChannel,
+        DeviceRequest,
         DeviceState,
-        MAX_CALIBRATION_POINTS,
+        MAX_PRE_TRIGGER_SAMPLES,
         MeasurementTaskStatus,
         ResponseCode,
     },
+    recording::{AutoRecordConfig, SessionRecorder},
+    reps::{RepDetectionConfig, RepDetector},
+    storage::{FIRMWARE_VERSION_SIZE, FaultRecord, HistoryEvent, HistoryRecord, StoredRecords},
+    transfer::{Transfer, TransferCommand, TransferCommandChannel},
+    trigger::ArmedTrigger,
+    workout::{Workout, WorkoutEvent},
 };
 
 pub mod ble;
 pub mod hx711;
 pub mod progressor;
+pub mod recording;
+pub mod storage;
+
+// Hardware independent, kept in the library so their tests run on the host
+pub use crimpdeq::{
+    calibration,
+    critical_force,
+    feedback,
+    filter,
+    reps,
+    transfer,
+    trigger,
+    workout,
+};
 
 // Helper macro for static allocation
 macro_rules! mk_static {
@@ -59,14 +96,7 @@ macro_rules! mk_static {
 }
 
 /// Static tracking the state of the device
-static DEVICE_STATE: Mutex<RefCell<DeviceState>> = Mutex::new(RefCell::new(DeviceState {
-    measurement_status: MeasurementTaskStatus::Disabled,
-    start_time: 0,
-    calibration_points: [(0.0, 0.0); MAX_CALIBRATION_POINTS],
-    calibration_point_count: 0,
-    battery_voltage: 4300,
-    ble_disconnection_time: None,
-}));
+static DEVICE_STATE: Mutex<RefCell<DeviceState>> = Mutex::new(RefCell::new(DeviceState::new()));
 
 // ESP-IDF App Descriptor
 esp_bootloader_esp_idf::esp_app_desc!();
@@ -97,7 +127,8 @@ async fn main(spawner: Spawner) -> ! {
     let connector = BleConnector::new(radio, bluetooth, Default::default()).unwrap();
     let controller: ExternalController<_, 1> = ExternalController::new(connector);
 
-    // Initialize load cell pins
+    // Initialize load cell pins, releasing the clock pin held through deep sleep
+    peripherals.GPIO5.rtcio_pad_hold(false);
     let clock_pin = Output::new(peripherals.GPIO5, Level::Low, OutputConfig::default());
     let data_pin = Input::new(
         peripherals.GPIO4,
@@ -105,8 +136,18 @@ async fn main(spawner: Spawner) -> ! {
     );
     let delay = Delay::new();
 
+    // Initialize chip temperature sensor, readings are not compensated without it
+    let temperature_sensor = TemperatureSensor::new(peripherals.TSENS, tsens::Config::default())
+        .inspect_err(|e| {
+            error!(
+                "Failed to initialize the temperature sensor: {:?}",
+                defmt::Debug2Format(e)
+            )
+        })
+        .ok();
+
     // Initialize Flash Storage
-    let flash = FlashStorage::new(peripherals.FLASH);
+    storage::init(FlashStorage::new(peripherals.FLASH));
 
     // Initialize RTC
     let rtc = Rtc::new(peripherals.LPWR);
@@ -152,6 +193,8 @@ async fn main(spawner: Spawner) -> ! {
 
     // Data point channel for communication between tasks
     let channel = mk_static!(DataPointChannel, Channel::new());
+    // Bulk transfer commands, from the GATT events to the transfer
+    let transfer_commands = mk_static!(TransferCommandChannel, Channel::new());
 
     // Start idle timer: if no BLE connection happens within TIMEOUT_MS, deep_sleep_task will sleep.
     critical_section::with(|cs| {
@@ -160,7 +203,13 @@ async fn main(spawner: Spawner) -> ! {
 
     // Spawn tasks
     spawner
-        .spawn(measurement_task(channel, clock_pin, data_pin, delay, flash))
+        .spawn(measurement_task(
+            channel,
+            clock_pin,
+            data_pin,
+            delay,
+            temperature_sensor,
+        ))
         .unwrap();
     spawner
         .spawn(battery_voltage_task(battery_adc, battery_pin))
@@ -175,11 +224,14 @@ async fn main(spawner: Spawner) -> ! {
                     critical_section::with(|cs| {
                         DEVICE_STATE.borrow_ref_mut(cs).on_ble_connected();
                     });
+                    // Drop commands of a transfer from a previous connection
+                    transfer_commands.clear();
                     // run until any task ends (usually because the connection has been closed),
                     // then return to advertising state.
-                    select(
-                        gatt_events_task(&server, &conn, channel),
+                    select3(
+                        gatt_events_task(&server, &conn, channel, transfer_commands),
                         data_processing_task(&server, &conn, channel),
+                        bulk_transfer_task(&server, &conn, transfer_commands),
                     )
                     .await;
                     critical_section::with(|cs| {
@@ -216,13 +268,20 @@ async fn ble_task<C: Controller, P: PacketPool>(mut runner: Runner<'_, C, P>) {
 
 #[embassy_executor::task]
 async fn deep_sleep_task(mut rtc: Rtc<'static>) {
-    const TIMEOUT_MS: u32 = 5 * 60 * 1000; // 5 minutes
+    const TIMEOUT_MS: u64 = 5 * 60 * 1000; // 5 minutes
+    const POWER_DOWN_TIMEOUT_MS: u64 = 1000;
 
     loop {
         let elapsed_ms = critical_section::with(|cs| {
-            DEVICE_STATE
-                .borrow_ref(cs)
-                .get_ble_disconnection_elapsed_ms()
+            let state = DEVICE_STATE.borrow_ref(cs);
+            // Stay awake while a session is recorded offline, or while waiting for a pull
+            // to record: deep sleep has no wake source to re-arm it. The load cell keeps
+            // sampling, so the battery drains much faster than in deep sleep
+            if state.recording || state.auto_record.enabled {
+                None
+            } else {
+                state.get_ble_disconnection_elapsed_ms()
+            }
         });
 
         if let Some(elapsed) = elapsed_ms {
@@ -233,7 +292,26 @@ async fn deep_sleep_task(mut rtc: Rtc<'static>) {
                     "Entering deep sleep after {} minutes of BLE disconnection",
                     TIMEOUT_MS / 60000
                 );
+                // Let the measurement task power down the load cell first
+                critical_section::with(|cs| {
+                    DEVICE_STATE
+                        .borrow_ref_mut(cs)
+                        .request(DeviceRequest::PowerDown)
+                });
+                let deadline = Instant::now() + Duration::from_millis(POWER_DOWN_TIMEOUT_MS);
+                while Instant::now() < deadline
+                    && !critical_section::with(|cs| {
+                        DEVICE_STATE.borrow_ref(cs).load_cell_powered_down
+                    })
+                {
+                    Timer::after(Duration::from_millis(10)).await;
+                }
                 Timer::after(Duration::from_millis(10)).await;
+
+                // GPIO levels are not kept in deep sleep, hold the clock pin high so the
+                // HX711 stays powered down
+                // SAFETY: Only the pad hold is changed, the pin stays with the load cell
+                unsafe { peripherals::GPIO5::steal() }.rtcio_pad_hold(true);
                 rtc.sleep_deep(&[]);
             }
         }
@@ -281,35 +359,378 @@ async fn measurement_task(
     clock_pin: Output<'static>,
     data_pin: Input<'static>,
     delay: Delay,
-    flash: FlashStorage<'static>,
+    temperature_sensor: Option<TemperatureSensor<'static>>,
 ) {
-    let mut load_cell = Hx711::new(data_pin, clock_pin, delay, flash);
-    load_cell.tare().await;
+    const TEMPERATURE_INTERVAL: Duration = Duration::from_secs(5);
+
+    let mut load_cell = Hx711::new(data_pin, clock_pin, delay);
+    let temperature_compensation =
+        storage::read_temperature_compensation().unwrap_or(TemperatureCompensation::DISABLED);
+    info!("Temperature compensation: {:?}", temperature_compensation);
+    load_cell.set_temperature_compensation(temperature_compensation);
+    critical_section::with(|cs| {
+        DEVICE_STATE.borrow_ref_mut(cs).temperature_compensation = temperature_compensation;
+    });
+    // Record the temperature of the initial tare
+    update_temperature(temperature_sensor.as_ref(), &mut load_cell);
+    let mut next_temperature_reading = Instant::now() + TEMPERATURE_INTERVAL;
+
+    if let Err(e) = load_cell.tare(DEFAULT_TARE_TOLERANCE).await {
+        // There is no previous zero to keep at boot, so take whatever is there
+        warn!(
+            "Initial tare failed: {:?}, accepting unstable readings",
+            defmt::Debug2Format(&e)
+        );
+        let _ = load_cell.tare(f32::INFINITY).await;
+    }
+
+    let filter_config = storage::read_filter_config().unwrap_or(FilterConfig::DISABLED);
+    info!("Measurement filter: {:?}", filter_config);
+    critical_section::with(|cs| {
+        DEVICE_STATE.borrow_ref_mut(cs).filter_config = filter_config;
+    });
+    let auto_record_config =
+        storage::read_auto_record_config().unwrap_or(AutoRecordConfig::DISABLED);
+    info!("Automatic recording: {:?}", auto_record_config);
+    critical_section::with(|cs| {
+        DEVICE_STATE.borrow_ref_mut(cs).auto_record = auto_record_config;
+    });
+    let rep_detection_config =
+        storage::read_rep_detection_config().unwrap_or(RepDetectionConfig::DISABLED);
+    info!("Rep detection: {:?}", rep_detection_config);
+    critical_section::with(|cs| {
+        DEVICE_STATE.borrow_ref_mut(cs).rep_detection = rep_detection_config;
+    });
+    let recorder = SessionRecorder::open()
+        .map_err(|e| {
+            error!(
+                "Failed to open session recorder: {:?}",
+                defmt::Debug2Format(&e)
+            )
+        })
+        .ok();
+    let mut pipeline = SamplePipeline {
+        filter: Filter::new(filter_config),
+        rep_detector: RepDetector::new(rep_detection_config),
+        feedback: TargetFeedback::new(TargetBandConfig::DISABLED),
+        critical_force_test: None,
+        auto_record: AutoRecording::new(auto_record_config),
+        recorder,
+    };
+
+    let mut workout: Option<Workout> = None;
+    let mut peak: Option<f32> = None;
+    let mut trigger: Option<ArmedTrigger> = None;
+    let mut clock = MeasurementClock::new(0);
+    let mut previous_status = MeasurementTaskStatus::Disabled;
+    let mut read_diagnostics = ReadDiagnostics::default();
+    let mut sensor_health = SensorHealth::Ok;
+    let mut sleep_requested = false;
 
     loop {
         // Get current device state
-        let (status, start_time) = critical_section::with(|cs| {
-            let state = DEVICE_STATE.borrow_ref(cs);
-            (state.measurement_status, state.start_time)
+        let (
+            status,
+            start_time,
+            requests,
+            tare_tolerance,
+            auto_zero,
+            filter_config,
+            research_mode,
+            temperature_compensation,
+            auto_record_config,
+            rep_detection_config,
+            target_band_config,
+            connected,
+        ) = critical_section::with(|cs| {
+            let mut state = DEVICE_STATE.borrow_ref_mut(cs);
+            (
+                state.measurement_status,
+                state.start_time,
+                core::mem::take(&mut state.requests),
+                state.tare_tolerance,
+                state.auto_zero,
+                state.filter_config,
+                state.research_mode,
+                state.temperature_compensation,
+                state.auto_record,
+                state.rep_detection,
+                state.target_band,
+                state.ble_disconnection_time.is_none(),
+            )
         });
+        // A triggered measurement was already reset when armed and keeps its history
+        let measurement_started = status.is_streaming()
+            && previous_status != status
+            && !(status == MeasurementTaskStatus::Enabled
+                && matches!(previous_status, MeasurementTaskStatus::Armed(..)));
+        previous_status = status;
+        clock.sync(start_time);
+        load_cell.set_auto_zero(auto_zero);
+
+        if filter_config != pipeline.filter.config() {
+            info!("Updating measurement filter: {:?}", filter_config);
+            pipeline.filter = Filter::new(filter_config);
+            if let Err(e) = storage::write_filter_config(&filter_config) {
+                error!(
+                    "Failed to persist filter configuration: {:?}",
+                    defmt::Debug2Format(&e)
+                );
+            }
+        }
+
+        if temperature_compensation != load_cell.temperature_compensation() {
+            info!(
+                "Updating temperature compensation: {:?}",
+                temperature_compensation
+            );
+            load_cell.set_temperature_compensation(temperature_compensation);
+            if let Err(e) = storage::write_temperature_compensation(&temperature_compensation) {
+                error!(
+                    "Failed to persist temperature compensation: {:?}",
+                    defmt::Debug2Format(&e)
+                );
+            }
+        }
+
+        if auto_record_config != pipeline.auto_record.config {
+            info!("Updating automatic recording: {:?}", auto_record_config);
+            pipeline.auto_record.config = auto_record_config;
+            if let Err(e) = storage::write_auto_record_config(&auto_record_config) {
+                error!(
+                    "Failed to persist automatic recording configuration: {:?}",
+                    defmt::Debug2Format(&e)
+                );
+            }
+        }
+
+        if rep_detection_config != pipeline.rep_detector.config() {
+            info!("Updating rep detection: {:?}", rep_detection_config);
+            pipeline.rep_detector = RepDetector::new(rep_detection_config);
+            if let Err(e) = storage::write_rep_detection_config(&rep_detection_config) {
+                error!(
+                    "Failed to persist rep detection configuration: {:?}",
+                    defmt::Debug2Format(&e)
+                );
+            }
+        }
+
+        if target_band_config != pipeline.feedback.config() {
+            info!("Updating target band: {:?}", target_band_config);
+            pipeline.feedback = TargetFeedback::new(target_band_config);
+        }
+
+        if Instant::now() >= next_temperature_reading {
+            update_temperature(temperature_sensor.as_ref(), &mut load_cell);
+            next_temperature_reading += TEMPERATURE_INTERVAL;
+        }
+
+        for request in requests {
+            match request {
+                DeviceRequest::Tare => {
+                    // Taring runs alongside the current status, fed by the regular reads
+                    if load_cell.start_tare(tare_tolerance).is_ok() {
+                        DataPoint::from(ResponseCode::TareStarted).send(channel);
+                    }
+                }
+                DeviceRequest::GetFaultLog => notify_fault_log(channel),
+                DeviceRequest::GetCalibrationHistory => notify_calibration_history(channel).await,
+                DeviceRequest::SaveFactoryCalibration => {
+                    let saved = load_cell.save_factory_calibration().is_ok();
+                    DataPoint::from(ResponseCode::FactoryCalibrationSaved(saved)).send(channel);
+                }
+                DeviceRequest::ClearFaultLog => {
+                    info!("Clearing fault log");
+                    if let Err(e) = storage::FAULT_LOG.clear() {
+                        error!("Failed to clear fault log: {:?}", defmt::Debug2Format(&e));
+                    }
+                }
+                DeviceRequest::ApplyCalibration => {
+                    let (calibration_points, calibration_point_count) =
+                        critical_section::with(|cs| {
+                            let state = DEVICE_STATE.borrow_ref(cs);
+                            (state.calibration_points, state.calibration_point_count)
+                        });
+                    apply_calibration(
+                        &mut load_cell,
+                        channel,
+                        &calibration_points[..calibration_point_count],
+                    );
+                }
+                DeviceRequest::StartRecording => {
+                    if let Some(recorder) = &mut pipeline.recorder {
+                        pipeline.auto_record.automatic = false;
+                        pipeline.filter.reset();
+                        start_recording(recorder, channel);
+                    }
+                }
+                DeviceRequest::StopRecording => {
+                    if let Some(recorder) = &mut pipeline.recorder {
+                        stop_recording(recorder, channel);
+                    }
+                }
+                DeviceRequest::ClearRecordings => {
+                    if let Some(recorder) = &mut pipeline.recorder {
+                        info!("Clearing recorded sessions");
+                        stop_recording(recorder, channel);
+                        if let Err(e) = recorder.clear().await {
+                            error!(
+                                "Failed to clear recorded sessions: {:?}",
+                                defmt::Debug2Format(&e)
+                            );
+                        }
+                    }
+                }
+                DeviceRequest::StartCriticalForceTest => {
+                    info!("Starting critical force test");
+                    pipeline.critical_force_test = Some(CriticalForceTest::new());
+                }
+                DeviceRequest::StopCriticalForceTest => {
+                    if pipeline.critical_force_test.take().is_some() {
+                        info!("Critical force test aborted");
+                    }
+                }
+                DeviceRequest::ResetPeak => {
+                    info!("Resetting peak");
+                    peak = None;
+                }
+                DeviceRequest::PowerDown => {
+                    // The device is about to sleep, keep the load cell down until then
+                    sleep_requested = true;
+                    load_cell.power_down();
+                }
+            }
+        }
+
+        if !status.is_streaming() && pipeline.critical_force_test.take().is_some() {
+            info!("Measurement stopped, critical force test aborted");
+        }
+        if !matches!(status, MeasurementTaskStatus::Workout(..)) && workout.take().is_some() {
+            info!("Measurement stopped, workout aborted");
+        }
+        if status != MeasurementTaskStatus::PeakHold {
+            peak = None;
+        }
+        if !matches!(status, MeasurementTaskStatus::Armed(..)) {
+            trigger = None;
+        }
+
+        let sampling_offline = !sleep_requested
+            && pipeline.recorder.as_ref().is_some_and(|recorder| {
+                recorder.is_recording() || pipeline.auto_record.is_armed(connected)
+            });
+
+        if measurement_started {
+            pipeline.reset();
+        }
 
         match status {
             MeasurementTaskStatus::Disabled => {
-                // Do nothing when disabled
+                // Keep sampling while recording or waiting for a pull to record, while a
+                // tare is pending or the sensor is unhealthy, so it can recover, otherwise
+                // power the load cell down until it is needed
+                if sampling_offline {
+                    match load_cell.read_calibrated().await {
+                        Ok(weight) => {
+                            let weight = pipeline.filter(weight, research_mode);
+                            pipeline.record(weight, connected, channel);
+                        }
+                        Err(e) => debug!("Skipping sample: {:?}", defmt::Debug2Format(&e)),
+                    }
+                } else if !sleep_requested
+                    && (load_cell.is_taring() || load_cell.health() != SensorHealth::Ok)
+                {
+                    let _ = load_cell.read_raw_value().await;
+                } else {
+                    load_cell.power_down();
+                }
             }
-            MeasurementTaskStatus::Tare => {
-                // Perform taring operation
-                load_cell.tare().await;
-
-                critical_section::with(|cs| {
-                    let mut state = DEVICE_STATE.borrow_ref_mut(cs);
-                    state.measurement_status = MeasurementTaskStatus::Disabled;
+            MeasurementTaskStatus::Enabled => match load_cell.read_calibrated().await {
+                Ok(weight) => {
+                    let weight = pipeline.process(weight, research_mode, connected, channel);
+                    send_weight_measurement(weight, &pipeline.feedback, &mut clock, channel);
+                }
+                Err(e) => debug!("Skipping measurement: {:?}", defmt::Debug2Format(&e)),
+            },
+            MeasurementTaskStatus::Workout(definition) => {
+                if workout.is_some_and(|current| current.definition() != definition) {
+                    workout = None;
+                }
+                let current = workout.get_or_insert_with(|| {
+                    info!("Starting workout: {:?}", definition);
+                    Workout::new(definition)
                 });
+
+                match load_cell.read_calibrated().await {
+                    Ok(weight) => {
+                        let weight = pipeline.process(weight, research_mode, connected, channel);
+                        send_weight_measurement(weight, &pipeline.feedback, &mut clock, channel);
+                        for event in current.update(weight, Instant::now()) {
+                            notify_workout_event(channel, event);
+                        }
+                    }
+                    Err(e) => debug!("Skipping measurement: {:?}", defmt::Debug2Format(&e)),
+                }
+
+                if current.is_completed() {
+                    info!("Workout completed");
+                    workout = None;
+                    critical_section::with(|cs| {
+                        let mut state = DEVICE_STATE.borrow_ref_mut(cs);
+                        if state.measurement_status == status {
+                            state.measurement_status = MeasurementTaskStatus::Disabled;
+                        }
+                    });
+                }
             }
-            MeasurementTaskStatus::Enabled => {
-                send_weight_measurement(&mut load_cell, start_time, channel).await;
+            MeasurementTaskStatus::PeakHold => match load_cell.read_calibrated().await {
+                Ok(weight) => {
+                    let weight = pipeline.process(weight, research_mode, connected, channel);
+                    if peak.is_none_or(|peak| weight > peak) {
+                        peak = Some(weight);
+                        let timestamp = clock.timestamp(channel);
+                        debug!("New peak: {}kg", weight);
+                        DataPoint::from(ResponseCode::PeakForce(weight, timestamp)).send(channel);
+                    }
+                }
+                Err(e) => debug!("Skipping measurement: {:?}", defmt::Debug2Format(&e)),
+            },
+            MeasurementTaskStatus::Armed(threshold, pre_trigger) => {
+                if trigger.is_some_and(|armed| !armed.is_armed_with(threshold, pre_trigger)) {
+                    trigger = None;
+                }
+                let armed =
+                    trigger.get_or_insert_with(|| ArmedTrigger::new(threshold, pre_trigger));
+
+                match load_cell.read_calibrated().await {
+                    Ok(weight) => {
+                        let weight = pipeline.process(weight, research_mode, connected, channel);
+                        let now = (time::Instant::now().duration_since_epoch()).as_micros();
+                        if armed.update(weight, now) {
+                            let triggered = critical_section::with(|cs| {
+                                let mut state = DEVICE_STATE.borrow_ref_mut(cs);
+                                if state.measurement_status != status {
+                                    return false;
+                                }
+                                // Timestamps start at the force onset
+                                state.start_time = now;
+                                state.measurement_status = MeasurementTaskStatus::Enabled;
+                                true
+                            });
+                            if triggered {
+                                info!("Measurement triggered at {} kg", weight);
+                                notify_pre_trigger(armed, channel, now).await;
+                                if pipeline.feedback.streams_weight() {
+                                    DataPoint::weight_measurement(weight, 0).send(channel);
+                                }
+                            }
+                            trigger = None;
+                        }
+                    }
+                    Err(e) => debug!("Skipping sample: {:?}", defmt::Debug2Format(&e)),
+                }
             }
-            MeasurementTaskStatus::Calibration(weight) => {
+            MeasurementTaskStatus::Calibration(weight, replace_index) => {
                 if !weight.is_finite() || weight < 0.0 {
                     error!("Ignoring invalid calibration weight: {}", weight);
                     critical_section::with(|cs| {
@@ -320,7 +741,20 @@ async fn measurement_task(
                 }
 
                 // Use the load cell's own calibration method to collect a calibration point
-                let calibration_point = load_cell.perform_calibration().await;
+                let calibration_point = match load_cell.perform_calibration().await {
+                    Ok(calibration_point) => calibration_point,
+                    Err(e) => {
+                        error!(
+                            "Failed to collect calibration point: {:?}",
+                            defmt::Debug2Format(&e)
+                        );
+                        critical_section::with(|cs| {
+                            DEVICE_STATE.borrow_ref_mut(cs).measurement_status =
+                                MeasurementTaskStatus::Disabled;
+                        });
+                        continue;
+                    }
+                };
                 if !calibration_point.is_finite() {
                     error!(
                         "Ignoring invalid calibration raw point: {}",
@@ -333,10 +767,20 @@ async fn measurement_task(
                     continue;
                 }
 
-                let (calibration_points, calibration_point_count) = critical_section::with(|cs| {
+                let calibration = critical_section::with(|cs| {
                     let mut state = DEVICE_STATE.borrow_ref_mut(cs);
+                    if state.measurement_status != status {
+                        info!("Calibration cancelled, discarding collected point");
+                        return None;
+                    }
+
                     let new_point: CalibrationPoint = (calibration_point, weight);
-                    if state.calibration_point_count < MAX_CALIBRATION_POINTS {
+                    if let Some(index) = replace_index
+                        && index < state.calibration_point_count
+                    {
+                        info!("Replacing calibration point {}", index);
+                        state.calibration_points[index] = new_point;
+                    } else if state.calibration_point_count < MAX_CALIBRATION_POINTS {
                         let index = state.calibration_point_count;
                         state.calibration_points[index] = new_point;
                         state.calibration_point_count += 1;
@@ -349,19 +793,36 @@ async fn measurement_task(
 
                     // Disable measurement mode after capturing point
                     state.measurement_status = MeasurementTaskStatus::Disabled;
-                    (state.calibration_points, state.calibration_point_count)
+                    Some((state.calibration_points, state.calibration_point_count))
                 });
+                let Some((calibration_points, calibration_point_count)) = calibration else {
+                    continue;
+                };
 
-                if calibration_point_count >= 2 {
-                    let points = &calibration_points[..calibration_point_count];
-                    if !load_cell.apply_multi_point_calibration(points) {
-                        error!("Failed to apply calibration points: {:?}", points);
-                    } else {
-                        notify_calibration_factor(channel, load_cell.current_calibration_factor());
-                        notify_calibration_points(channel, points);
+                apply_calibration(
+                    &mut load_cell,
+                    channel,
+                    &calibration_points[..calibration_point_count],
+                );
+            }
+            MeasurementTaskStatus::Verification(reference, tolerance) => {
+                let measured = load_cell.measure_weight().await;
+                let cancelled = critical_section::with(|cs| {
+                    let mut state = DEVICE_STATE.borrow_ref_mut(cs);
+                    if state.measurement_status != status {
+                        return true;
                     }
-                } else {
-                    info!("Calibration needs at least two points before applying.");
+                    state.measurement_status = MeasurementTaskStatus::Disabled;
+                    false
+                });
+                if cancelled {
+                    info!("Calibration check cancelled");
+                    continue;
+                }
+
+                match measured {
+                    Ok(measured) => notify_verification(channel, reference, tolerance, measured),
+                    Err(e) => error!("Failed to check calibration: {:?}", defmt::Debug2Format(&e)),
                 }
             }
             MeasurementTaskStatus::DefaultCalibration => {
@@ -372,7 +833,14 @@ async fn measurement_task(
                         defmt::Debug2Format(&e)
                     );
                 } else {
-                    notify_calibration_factor(channel, load_cell.current_calibration_factor());
+                    let factor = load_cell.current_calibration_factor();
+                    notify_calibration_factor(channel, factor);
+                    log_calibration_event(HistoryEvent::Calibration {
+                        model: load_cell.calibration_model().kind(),
+                        factor,
+                        points: [(0.0, 0.0); MAX_CALIBRATION_POINTS],
+                        point_count: 0,
+                    });
                 }
                 critical_section::with(|cs| {
                     let mut state = DEVICE_STATE.borrow_ref_mut(cs);
@@ -384,6 +852,10 @@ async fn measurement_task(
                 match load_cell.get_calibration_factor() {
                     Ok(factor) => {
                         DataPoint::from(ResponseCode::CalibrationFactor(factor)).send(channel);
+                        DataPoint::from(ResponseCode::CalibrationModel(
+                            load_cell.calibration_model().kind(),
+                        ))
+                        .send(channel);
                     }
                     Err(e) => {
                         error!(
@@ -412,22 +884,116 @@ async fn measurement_task(
             }
         }
 
-        // Add a short delay to prevent tight loops
-        if status == MeasurementTaskStatus::Disabled {
+        match load_cell.take_tare_result() {
+            Some(Ok(noise)) => DataPoint::from(ResponseCode::TareCompleted(noise)).send(channel),
+            Some(Err(Hx711Error::UnstableTare(noise))) => {
+                DataPoint::from(ResponseCode::TareFailed(noise)).send(channel)
+            }
+            Some(Err(e)) => error!("Tare failed: {:?}", defmt::Debug2Format(&e)),
+            None => {}
+        }
+
+        let health = load_cell.health();
+        if health != sensor_health {
+            sensor_health = health;
+            on_sensor_health_changed(channel, health);
+        }
+
+        let diagnostics = load_cell.diagnostics();
+        if diagnostics != read_diagnostics {
+            read_diagnostics = diagnostics;
+            critical_section::with(|cs| {
+                DEVICE_STATE.borrow_ref_mut(cs).read_diagnostics = diagnostics;
+            });
+        }
+
+        let powered_down = load_cell.is_powered_down();
+        critical_section::with(|cs| {
+            DEVICE_STATE.borrow_ref_mut(cs).load_cell_powered_down = powered_down;
+        });
+
+        // Add a short delay to prevent tight loops, offline samples wait for the load cell
+        if status == MeasurementTaskStatus::Disabled
+            && !sampling_offline
+            && (!load_cell.is_taring() || sleep_requested)
+        {
             Timer::after(Duration::from_millis(10)).await;
         }
     }
 }
 
-/// Send a weight measurement data point with current timestamp
-async fn send_weight_measurement(
+/// Read the chip temperature and share it with the load cell and the device state
+///
+/// Without a temperature sensor the temperature stays unknown and nothing is compensated.
+fn update_temperature(
+    temperature_sensor: Option<&TemperatureSensor<'_>>,
     load_cell: &mut Hx711<'_>,
-    start_time: u32,
+) {
+    let Some(temperature_sensor) = temperature_sensor else {
+        return;
+    };
+    let temperature = temperature_sensor.get_temperature().to_celsius();
+    debug!("Temperature: {} °C", temperature);
+    load_cell.set_temperature(Some(temperature));
+    critical_section::with(|cs| {
+        DEVICE_STATE.borrow_ref_mut(cs).temperature = Some(temperature);
+    });
+}
+
+/// Clock of the measurement timestamps
+///
+/// The Tindeq timestamps are `u32` microseconds and wrap around after about 71 minutes.
+/// The elapsed time is kept in `u64` and an epoch marker is sent before the first
+/// timestamp of every wrap, so clients can rebuild the full time of long sessions.
+struct MeasurementClock {
+    /// Start time of the measurement in microseconds since boot
+    start_time: u64,
+    /// Number of wraps of the timestamps sent so far
+    epoch: u32,
+}
+
+impl MeasurementClock {
+    fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            epoch: 0,
+        }
+    }
+
+    /// Follow the start time of the device state, restarting the epochs when it changes
+    fn sync(&mut self, start_time: u64) {
+        if start_time != self.start_time {
+            *self = Self::new(start_time);
+        }
+    }
+
+    /// Get the current timestamp, sending an epoch marker first if it wrapped around
+    fn timestamp(&mut self, channel: &'static DataPointChannel) -> u32 {
+        let now = (time::Instant::now().duration_since_epoch()).as_micros();
+        let elapsed = now.saturating_sub(self.start_time);
+        let epoch = (elapsed >> u32::BITS) as u32;
+        if epoch != self.epoch {
+            info!("Measurement timestamps wrapped, epoch {}", epoch);
+            self.epoch = epoch;
+            DataPoint::from(ResponseCode::TimestampEpoch(epoch)).send(channel);
+        }
+        elapsed as u32
+    }
+}
+
+/// Send a weight measurement data point with current timestamp
+///
+/// Nothing is sent if the target band events of `feedback` replace the measurements.
+fn send_weight_measurement(
+    weight: f32,
+    feedback: &TargetFeedback,
+    clock: &mut MeasurementClock,
     channel: &'static DataPointChannel,
 ) {
-    let weight = load_cell.read_calibrated().await;
-    let now = (time::Instant::now().duration_since_epoch()).as_micros() as u32;
-    let timestamp = now.wrapping_sub(start_time);
+    if !feedback.streams_weight() {
+        return;
+    }
+    let timestamp = clock.timestamp(channel);
 
     debug!(
         "Sending measurement: Weight: {}kg, Timestamp: {:?}",
@@ -438,6 +1004,415 @@ async fn send_weight_measurement(
     DataPoint::weight_measurement(weight, timestamp).send(channel);
 }
 
+/// Processing of the calibrated samples, shared by every streaming mode
+///
+/// Each sample is filtered, unless research mode streams the unfiltered samples, then
+/// goes through rep detection, the target band, the critical force test and the session
+/// recording, so these behave the same whichever mode streams the samples.
+struct SamplePipeline {
+    filter: Filter,
+    rep_detector: RepDetector,
+    feedback: TargetFeedback,
+    critical_force_test: Option<CriticalForceTest>,
+    auto_record: AutoRecording,
+    /// Session recorder, None if the recording area could not be opened
+    recorder: Option<SessionRecorder>,
+}
+
+impl SamplePipeline {
+    /// Forget the previous samples when a measurement starts
+    fn reset(&mut self) {
+        self.filter.reset();
+        self.rep_detector.reset();
+        self.feedback.reset();
+    }
+
+    /// Filter a sample, unless in research mode
+    fn filter(&mut self, weight: f32, research_mode: bool) -> f32 {
+        if research_mode {
+            weight
+        } else {
+            self.filter.apply(weight)
+        }
+    }
+
+    /// Record a sample, see [`AutoRecording::record`]
+    fn record(&mut self, weight: f32, connected: bool, channel: &'static DataPointChannel) {
+        if let Some(recorder) = &mut self.recorder {
+            self.auto_record
+                .record(recorder, weight, connected, channel);
+        }
+    }
+
+    /// Process a sample of a streaming mode, sending the events it causes and
+    /// returning the weight to stream
+    fn process(
+        &mut self,
+        weight: f32,
+        research_mode: bool,
+        connected: bool,
+        channel: &'static DataPointChannel,
+    ) -> f32 {
+        let weight = self.filter(weight, research_mode);
+        let now = Instant::now();
+        if let Some(summary) = self.rep_detector.update(weight, now) {
+            info!("Rep completed: {:?}", summary);
+            DataPoint::from(ResponseCode::RepSummary(summary)).send(channel);
+        }
+        if let Some(position) = self.feedback.update(weight, now) {
+            DataPoint::from(ResponseCode::TargetBand(position)).send(channel);
+        }
+        if let Some(test) = &mut self.critical_force_test {
+            for event in test.update(weight, now) {
+                notify_critical_force_event(channel, event);
+            }
+            if test.is_finished() {
+                self.critical_force_test = None;
+            }
+        }
+        self.record(weight, connected, channel);
+        weight
+    }
+}
+
+/// Send a critical force test event to the client
+fn notify_critical_force_event(channel: &'static DataPointChannel, event: TestEvent) {
+    let response = match event {
+        TestEvent::Cue(rep, phase) => ResponseCode::CriticalForceCue(rep, phase),
+        TestEvent::RepMean(rep, mean) => ResponseCode::CriticalForceRep(rep, mean),
+        TestEvent::Completed(result) => {
+            info!("Critical force test completed: {:?}", result);
+            ResponseCode::CriticalForceResult(result)
+        }
+    };
+    DataPoint::from(response).send(channel);
+}
+
+/// Send the samples buffered before the force onset at `onset`, oldest first
+///
+/// Sending waits for room in the channel, as the whole pre-trigger time is sent at once.
+async fn notify_pre_trigger(
+    trigger: &ArmedTrigger,
+    channel: &'static DataPointChannel,
+    onset: u64,
+) {
+    const SEND_TIMEOUT: Duration = Duration::from_millis(100);
+
+    let mut samples = [(0.0, 0); MAX_PRE_TRIGGER_SAMPLES];
+    let mut len = 0;
+    let mut pending = trigger.pre_trigger_samples(onset).peekable();
+    while let Some(sample) = pending.next() {
+        samples[len] = sample;
+        len += 1;
+        if len == MAX_PRE_TRIGGER_SAMPLES || pending.peek().is_none() {
+            let data_point = DataPoint::from(ResponseCode::PreTriggerSamples(len as u8, samples));
+            if !data_point.send_timeout(channel, SEND_TIMEOUT).await {
+                return;
+            }
+            len = 0;
+        }
+    }
+}
+
+/// Send a workout event to the client
+fn notify_workout_event(channel: &'static DataPointChannel, event: WorkoutEvent) {
+    let response = match event {
+        WorkoutEvent::Phase(set, rep, phase, duration) => {
+            ResponseCode::WorkoutPhase(set, rep, phase, duration)
+        }
+        WorkoutEvent::Rep(set, rep, mean, compliance) => {
+            ResponseCode::WorkoutRep(set, rep, mean, compliance)
+        }
+    };
+    DataPoint::from(response).send(channel);
+}
+
+/// Automatic start and stop of the session recording
+struct AutoRecording {
+    /// Automatic recording configuration
+    config: AutoRecordConfig,
+    /// The session being recorded was started automatically
+    automatic: bool,
+    /// Last time the weight reached the threshold
+    last_load: Instant,
+}
+
+impl AutoRecording {
+    fn new(config: AutoRecordConfig) -> Self {
+        Self {
+            config,
+            automatic: false,
+            last_load: Instant::now(),
+        }
+    }
+
+    /// Check if a pull would start a recording
+    fn is_armed(&self, connected: bool) -> bool {
+        self.config.enabled && !connected
+    }
+
+    /// Record a sample if a session is being recorded, starting a session on a pull
+    /// while no client is connected and stopping an automatic one once idle
+    fn record(
+        &mut self,
+        recorder: &mut SessionRecorder,
+        weight: f32,
+        connected: bool,
+        channel: &'static DataPointChannel,
+    ) {
+        let loaded = weight >= self.config.threshold;
+        if loaded {
+            self.last_load = Instant::now();
+        }
+
+        if !recorder.is_recording() {
+            if !(loaded && self.is_armed(connected)) {
+                return;
+            }
+            info!("Pull detected without a client, recording");
+            self.automatic = true;
+            start_recording(recorder, channel);
+        }
+
+        if let Err(e) = recorder.record(weight) {
+            error!("Failed to record sample: {:?}", defmt::Debug2Format(&e));
+            stop_recording(recorder, channel);
+            return;
+        }
+
+        if self.automatic && self.last_load.elapsed() >= self.config.idle_time {
+            info!(
+                "No pull for {} s, stopping",
+                self.config.idle_time.as_secs()
+            );
+            stop_recording(recorder, channel);
+        }
+    }
+}
+
+/// Start recording a session, notifying its ID
+fn start_recording(recorder: &mut SessionRecorder, channel: &'static DataPointChannel) {
+    match recorder.start() {
+        Ok(session) => {
+            critical_section::with(|cs| DEVICE_STATE.borrow_ref_mut(cs).recording = true);
+            DataPoint::from(ResponseCode::RecordingStarted(session)).send(channel);
+        }
+        Err(e) => error!("Failed to start recording: {:?}", defmt::Debug2Format(&e)),
+    }
+}
+
+/// Stop recording the current session, if any, notifying its sample count
+fn stop_recording(recorder: &mut SessionRecorder, channel: &'static DataPointChannel) {
+    if let Some((session, samples)) = recorder.stop() {
+        critical_section::with(|cs| DEVICE_STATE.borrow_ref_mut(cs).on_recording_stopped());
+        DataPoint::from(ResponseCode::RecordingStopped(session, samples)).send(channel);
+    }
+}
+
+/// Notify the client of a sensor health change and log faults
+fn on_sensor_health_changed(channel: &'static DataPointChannel, health: SensorHealth) {
+    DataPoint::from(ResponseCode::SensorHealth(health)).send(channel);
+    if health == SensorHealth::Ok {
+        info!("Load cell recovered");
+        return;
+    }
+
+    warn!("Load cell fault: {:?}", health);
+    let record = FaultRecord {
+        health,
+        uptime_ms: (time::Instant::now().duration_since_epoch()).as_millis() as u32,
+    };
+    if let Err(e) = storage::FAULT_LOG.append(&record.to_bytes()) {
+        error!("Failed to log fault: {:?}", defmt::Debug2Format(&e));
+    }
+}
+
+/// Send the result of a calibration check and record it in the calibration history
+fn notify_verification(
+    channel: &'static DataPointChannel,
+    reference: f32,
+    tolerance: f32,
+    measured: f32,
+) {
+    let error = measured - reference;
+    let passed = error.abs() <= tolerance;
+    if passed {
+        info!(
+            "Calibration check passed: {} kg for {} kg",
+            measured, reference
+        );
+    } else {
+        warn!(
+            "Calibration check failed: {} kg for {} kg",
+            measured, reference
+        );
+    }
+    DataPoint::from(ResponseCode::VerificationResult(measured, error, passed)).send(channel);
+
+    log_calibration_event(HistoryEvent::Verification {
+        reference,
+        measured,
+        passed,
+    });
+}
+
+/// Append an event to the calibration history
+fn log_calibration_event(event: HistoryEvent) {
+    let temperature = critical_section::with(|cs| DEVICE_STATE.borrow_ref(cs).temperature);
+    let uptime_ms = (time::Instant::now().duration_since_epoch()).as_millis() as u32;
+    let record = HistoryRecord::new(event, uptime_ms, temperature);
+    if let Err(e) = storage::CALIBRATION_HISTORY.append(&record.to_bytes()) {
+        error!(
+            "Failed to record calibration event: {:?}",
+            defmt::Debug2Format(&e)
+        );
+    }
+}
+
+/// Send the calibration history entries, oldest first
+///
+/// Each entry is followed by its event details. An empty log is reported as a single
+/// entry with a count of 0. Sending waits for room in the channel, as a full history
+/// does not fit, and stops if the receiver does not keep up.
+async fn notify_calibration_history(channel: &'static DataPointChannel) {
+    const SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+    let count = match storage::CALIBRATION_HISTORY.len() {
+        Ok(count) => count,
+        Err(e) => {
+            error!(
+                "Failed to read calibration history: {:?}",
+                defmt::Debug2Format(&e)
+            );
+            return;
+        }
+    };
+
+    if count == 0 {
+        DataPoint::from(ResponseCode::CalibrationHistoryEntry(
+            0,
+            0,
+            0,
+            0,
+            f32::NAN,
+            [0; FIRMWARE_VERSION_SIZE],
+        ))
+        .send(channel);
+        return;
+    }
+
+    for index in 0..count {
+        let record = match storage::CALIBRATION_HISTORY.read(index) {
+            Ok(Some(bytes)) => HistoryRecord::from_bytes(&bytes),
+            Ok(None) => break,
+            Err(e) => {
+                error!(
+                    "Failed to read calibration history: {:?}",
+                    defmt::Debug2Format(&e)
+                );
+                break;
+            }
+        };
+        let Some(record) = record else {
+            continue;
+        };
+
+        let index = index as u8;
+        let entry = DataPoint::from(ResponseCode::CalibrationHistoryEntry(
+            index,
+            count as u8,
+            record.event.kind(),
+            record.uptime_ms,
+            record.temperature.unwrap_or(f32::NAN),
+            record.firmware_version,
+        ));
+        if !entry.send_timeout(channel, SEND_TIMEOUT).await {
+            return;
+        }
+
+        match record.event {
+            HistoryEvent::Calibration {
+                model,
+                factor,
+                points,
+                point_count,
+            } => {
+                let details = DataPoint::from(ResponseCode::HistoryCalibration(
+                    index,
+                    model,
+                    factor,
+                    point_count as u8,
+                ));
+                if !details.send_timeout(channel, SEND_TIMEOUT).await {
+                    return;
+                }
+                for (point_index, (raw_value, weight)) in points[..point_count].iter().enumerate() {
+                    let point = DataPoint::from(ResponseCode::HistoryCalibrationPoint(
+                        index,
+                        point_index as u8,
+                        *raw_value,
+                        *weight,
+                    ));
+                    if !point.send_timeout(channel, SEND_TIMEOUT).await {
+                        return;
+                    }
+                }
+            }
+            HistoryEvent::Verification {
+                reference,
+                measured,
+                passed,
+            } => {
+                let details = DataPoint::from(ResponseCode::HistoryVerification(
+                    index, reference, measured, passed,
+                ));
+                if !details.send_timeout(channel, SEND_TIMEOUT).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Send the fault log entries, oldest first
+///
+/// An empty log is reported as a single entry with a count of 0.
+fn notify_fault_log(channel: &'static DataPointChannel) {
+    let count = match storage::FAULT_LOG.len() {
+        Ok(count) => count,
+        Err(e) => {
+            error!("Failed to read fault log: {:?}", defmt::Debug2Format(&e));
+            return;
+        }
+    };
+
+    if count == 0 {
+        DataPoint::from(ResponseCode::FaultLogEntry(0, 0, SensorHealth::Ok, 0)).send(channel);
+        return;
+    }
+
+    for index in 0..count {
+        match storage::FAULT_LOG.read(index) {
+            Ok(Some(bytes)) => {
+                if let Some(record) = FaultRecord::from_bytes(&bytes) {
+                    DataPoint::from(ResponseCode::FaultLogEntry(
+                        index as u8,
+                        count as u8,
+                        record.health,
+                        record.uptime_ms,
+                    ))
+                    .send(channel);
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                error!("Failed to read fault log: {:?}", defmt::Debug2Format(&e));
+                break;
+            }
+        }
+    }
+}
+
 fn notify_calibration_points(
     channel: &'static DataPointChannel,
     calibration_points: &[CalibrationPoint],
@@ -448,6 +1423,65 @@ fn notify_calibration_points(
     }
 }
 
+/// Fit the calibration points and apply the model, notifying the result
+fn apply_calibration(
+    load_cell: &mut Hx711<'_>,
+    channel: &'static DataPointChannel,
+    calibration_points: &[CalibrationPoint],
+) {
+    if calibration_points.len() < MIN_CALIBRATION_POINTS {
+        info!("Calibration needs at least two points before applying.");
+        return;
+    }
+
+    match load_cell.apply_multi_point_calibration(calibration_points) {
+        Ok(fit) => {
+            let factor = load_cell.current_calibration_factor();
+            notify_calibration_factor(channel, factor);
+            notify_calibration_points(channel, calibration_points);
+            notify_calibration_fit(channel, &fit, calibration_points);
+
+            let mut points = [(0.0, 0.0); MAX_CALIBRATION_POINTS];
+            points[..calibration_points.len()].copy_from_slice(calibration_points);
+            log_calibration_event(HistoryEvent::Calibration {
+                model: fit.model.kind(),
+                factor,
+                points,
+                point_count: calibration_points.len(),
+            });
+        }
+        Err(_) => error!(
+            "Failed to apply calibration points: {:?}",
+            calibration_points
+        ),
+    }
+}
+
+/// Send the calibration model type, the residual of each fitted point and the quality report
+fn notify_calibration_fit(
+    channel: &'static DataPointChannel,
+    fit: &CalibrationFit,
+    calibration_points: &[CalibrationPoint],
+) {
+    DataPoint::from(ResponseCode::CalibrationModel(fit.model.kind())).send(channel);
+    for (index, residual) in fit.residuals[..calibration_points.len()].iter().enumerate() {
+        // Skipped points have no residual
+        if residual.is_finite() {
+            debug!("Calibration point {} residual: {} kg", index, residual);
+            DataPoint::from(ResponseCode::CalibrationResidual(index as u8, *residual))
+                .send(channel);
+        }
+    }
+
+    let report = fit.report(calibration_points);
+    if report.passed {
+        info!("Calibration passed: {:?}", report);
+    } else {
+        warn!("Calibration failed: {:?}", report);
+    }
+    DataPoint::from(ResponseCode::CalibrationReport(report)).send(channel);
+}
+
 fn notify_calibration_factor(channel: &'static DataPointChannel, calibration_factor: f32) {
     debug!("Notifying calibration factor: {:?}", calibration_factor);
     DataPoint::from(ResponseCode::CalibrationFactor(calibration_factor)).send(channel);
@@ -461,8 +1495,10 @@ async fn gatt_events_task<P: PacketPool>(
     server: &Server<'_>,
     conn: &GattConnection<'_, '_, P>,
     channel: &'static DataPointChannel,
+    transfer_commands: &'static TransferCommandChannel,
 ) -> Result<(), Error> {
     let control_point = server.progressor.control_point;
+    let transfer_control = server.bulk_transfer.transfer_control;
     loop {
         match conn.next().await {
             GattConnectionEvent::Disconnected { reason } => {
@@ -488,6 +1524,21 @@ async fn gatt_events_task<P: PacketPool>(
                     });
                 }
 
+                // Forward bulk transfer commands to the transfer
+                if let GattEvent::Write(write_event) = &event
+                    && write_event.handle() == transfer_control.handle
+                {
+                    match TransferCommand::from_bytes(write_event.data()) {
+                        Some(command) => {
+                            info!("Transfer Control Received: {:?}", command);
+                            if transfer_commands.try_send(command).is_err() {
+                                warn!("Too many pending transfer commands, ignoring {:?}", command);
+                            }
+                        }
+                        None => warn!("Invalid Transfer Control command"),
+                    }
+                }
+
                 // Ensure reply is sent
                 if let Ok(reply) = event.accept() {
                     reply.send().await;
@@ -527,3 +1578,81 @@ async fn data_processing_task<P: PacketPool>(
         }
     }
 }
+
+/// Send the chunks of the requested bulk transfers to the client
+///
+/// At most a window of chunks is sent ahead of the acknowledged offset. If no
+/// acknowledgement arrives in time, the unacknowledged chunks are sent again.
+async fn bulk_transfer_task<P: PacketPool>(
+    server: &Server<'_>,
+    conn: &GattConnection<'_, '_, P>,
+    transfer_commands: &'static TransferCommandChannel,
+) {
+    const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+    let transfer_data = server.bulk_transfer.transfer_data;
+    let mut transfer: Option<Transfer<StoredRecords>> = None;
+
+    loop {
+        let command = match &mut transfer {
+            None => Some(transfer_commands.receive().await),
+            Some(transfer) if transfer.can_send() => transfer_commands.try_receive().ok(),
+            // Window full, wait for an acknowledgement
+            Some(transfer) => match with_timeout(ACK_TIMEOUT, transfer_commands.receive()).await {
+                Ok(command) => Some(command),
+                Err(_) => {
+                    debug!("Transfer acknowledgement timed out, sending again");
+                    transfer.rewind();
+                    None
+                }
+            },
+        };
+
+        match command {
+            Some(TransferCommand::Start {
+                resource,
+                offset,
+                chunk_size,
+                window,
+                generation,
+            }) => match StoredRecords::open(resource).and_then(|records| {
+                Transfer::start(resource, records, offset, chunk_size, window, generation)
+            }) {
+                Ok(started) => transfer = Some(started),
+                Err(e) => {
+                    error!("Failed to start transfer: {:?}", defmt::Debug2Format(&e));
+                    transfer = None;
+                }
+            },
+            Some(TransferCommand::Ack(offset)) => {
+                if let Some(transfer) = &mut transfer {
+                    transfer.ack(offset);
+                }
+            }
+            Some(TransferCommand::Abort) => {
+                info!("Transfer aborted");
+                transfer = None;
+            }
+            None => {}
+        }
+
+        let Some(current) = &mut transfer else {
+            continue;
+        };
+        if current.is_finished() {
+            info!("Transfer finished");
+            transfer = None;
+            continue;
+        }
+        if current.can_send() {
+            let chunk = current.next_chunk();
+            if let Err(e) = transfer_data.notify(conn, &chunk).await {
+                info!(
+                    "Error sending transfer chunk: {:?}",
+                    defmt::Debug2Format(&e)
+                );
+                break;
+            }
+        }
+    }
+}
@@ -3,26 +3,58 @@
 /// See [Tindeq API documentation] for more information
 ///
 /// [Tindeq API documentation]: https://tindeq.com/progressor_api/
+use arrayvec::ArrayVec;
 use defmt::{Format, error, info, trace, warn};
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, channel::Channel};
+use embassy_time::{Duration, with_timeout};
 use esp_hal::time;
 use trouble_host::types::gatt_traits::{AsGatt, FromGatt, FromGattError};
 
+use crate::{
+    calibration::{
+        self,
+        CalibrationPoint,
+        CalibrationReport,
+        MAX_CALIBRATION_POINTS,
+        MIN_CALIBRATION_POINTS,
+        ModelKind,
+    },
+    critical_force::{CriticalForceResult, TestPhase},
+    feedback::{BandPosition, TargetBandConfig},
+    filter::FilterConfig,
+    hx711::{
+        AutoZeroConfig,
+        DEFAULT_TARE_TOLERANCE,
+        ReadDiagnostics,
+        SensorHealth,
+        TemperatureCompensation,
+    },
+    recording::AutoRecordConfig,
+    reps::{RepDetectionConfig, RepSummary},
+    storage::FIRMWARE_VERSION_SIZE,
+    trigger::{DEFAULT_PRE_TRIGGER_TIME, MAX_PRE_TRIGGER_TIME},
+    workout::{WorkoutDefinition, WorkoutPhase},
+};
+
 /// Size of the channel used to send data points
 const DATA_POINT_COMMAND_CHANNEL_SIZE: usize = 80;
 /// Channel used to send data points
 pub type DataPointChannel = Channel<NoopRawMutex, DataPoint, DATA_POINT_COMMAND_CHANNEL_SIZE>;
 
 /// Maximum size of the data payload in bytes for any data point
-pub const MAX_PAYLOAD_SIZE: usize = 10;
+///
+/// A data point (payload + 2 header bytes) still fits in a single notification with
+/// the default ATT MTU of 23 bytes.
+pub const MAX_PAYLOAD_SIZE: usize = 18;
 
 /// Number of bytes in the device ID
 const DEVICE_ID_SIZE: usize = 6;
-/// Maximum number of calibration points to store
-pub const MAX_CALIBRATION_POINTS: usize = 20;
-
-/// Calibration point storing raw value and known weight
-pub type CalibrationPoint = (f32, f32);
+/// Number of bytes in the calibration curve
+const CALIBRATION_CURVE_SIZE: usize = 12;
+/// Maximum number of pre-trigger samples in a data point
+pub const MAX_PRE_TRIGGER_SAMPLES: usize = 2;
+/// Maximum number of requests waiting for the measurement task
+const MAX_PENDING_REQUESTS: usize = 4;
 
 /// Status of the weight measurement task
 #[derive(Copy, Debug, Clone, PartialEq)]
@@ -31,14 +63,64 @@ pub enum MeasurementTaskStatus {
     Enabled,
     /// Measurements are disabled
     Disabled,
-    /// Device is in calibration mode with target weight
-    Calibration(f32),
-    /// Taring the scale (used in ClimbHarder App)
-    Tare,
-    /// Restores default calibration values
+    /// Device is in calibration mode with target weight, replacing the point at the
+    /// given index if any
+    Calibration(f32, Option<usize>),
+    /// Device is checking the calibration against a reference weight, with a tolerance
+    /// in kg
+    Verification(f32, f32),
+    /// Restores the factory calibration, or the default values if there is none
     DefaultCalibration,
     /// Get the calibration values
     GetCalibration,
+    /// Measurements are enabled and timed by the given workout
+    Workout(WorkoutDefinition),
+    /// Only the running maximum weight is sent, when it increases
+    PeakHold,
+    /// Measurements start when the weight reaches the threshold in kg, preceded by
+    /// the samples of the pre-trigger time
+    Armed(f32, Duration),
+}
+
+impl MeasurementTaskStatus {
+    /// Check if samples are streamed to the client in this status
+    pub fn is_streaming(&self) -> bool {
+        matches!(
+            self,
+            Self::Enabled | Self::Workout(..) | Self::PeakHold | Self::Armed(..)
+        )
+    }
+}
+
+/// One-shot requests served by the measurement task alongside the current measurement status
+#[derive(Copy, Debug, Clone, PartialEq, Format)]
+pub enum DeviceRequest {
+    /// Tare the scale (used in ClimbHarder App)
+    Tare,
+    /// Send the fault log entries
+    GetFaultLog,
+    /// Clear the fault log
+    ClearFaultLog,
+    /// Fit the calibration points again after they were edited
+    ApplyCalibration,
+    /// Power down the load cell ahead of deep sleep
+    PowerDown,
+    /// Send the calibration history entries
+    GetCalibrationHistory,
+    /// Save the calibration in use as the factory calibration
+    SaveFactoryCalibration,
+    /// Start recording a session to flash
+    StartRecording,
+    /// Stop recording the current session
+    StopRecording,
+    /// Erase the recorded sessions
+    ClearRecordings,
+    /// Start a critical force test on the measurement stream
+    StartCriticalForceTest,
+    /// Abort the critical force test in progress
+    StopCriticalForceTest,
+    /// Forget the peak held so far
+    ResetPeak,
 }
 
 /// Device state management
@@ -46,58 +128,173 @@ pub enum MeasurementTaskStatus {
 pub struct DeviceState {
     /// Measurement status
     pub measurement_status: MeasurementTaskStatus,
-    /// Start time of the measurement in microseconds
-    pub start_time: u32,
+    /// Start time of the measurement in microseconds since boot
+    pub start_time: u64,
+    /// Requests waiting for the measurement task, in arrival order
+    pub requests: ArrayVec<DeviceRequest, MAX_PENDING_REQUESTS>,
+    /// Maximum noise accepted while taring, in kg
+    pub tare_tolerance: f32,
+    /// Automatic zero tracking configuration, disabled when None
+    pub auto_zero: Option<AutoZeroConfig>,
+    /// Filter applied to the measurement stream
+    pub filter_config: FilterConfig,
+    /// Research mode, streams unfiltered measurements
+    pub research_mode: bool,
+    /// Temperature compensation of the load cell drift
+    pub temperature_compensation: TemperatureCompensation,
+    /// Automatic recording of pulls while no client is connected
+    pub auto_record: AutoRecordConfig,
+    /// A session is being recorded to flash
+    pub recording: bool,
+    /// Repetition detection in the measurement stream
+    pub rep_detection: RepDetectionConfig,
+    /// Uploaded workout, None until one is uploaded
+    pub workout: Option<WorkoutDefinition>,
+    /// Target force band events while measuring
+    pub target_band: TargetBandConfig,
     /// Calibration points (raw value, weight)
     pub calibration_points: [CalibrationPoint; MAX_CALIBRATION_POINTS],
     /// Number of calibration points currently stored
     pub calibration_point_count: usize,
     /// Battery voltage in millivolts
     pub battery_voltage: u32,
+    /// Chip temperature in °C, None until the first reading
+    pub temperature: Option<f32>,
+    /// Load cell glitch and outlier counters
+    pub read_diagnostics: ReadDiagnostics,
+    /// Load cell in power-down mode
+    pub load_cell_powered_down: bool,
     /// BLE disconnection time in milliseconds (None when connected)
-    pub ble_disconnection_time: Option<u32>,
+    pub ble_disconnection_time: Option<u64>,
 }
 
 impl Default for DeviceState {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceState {
+    /// Create the initial device state
+    pub const fn new() -> Self {
         Self {
             measurement_status: MeasurementTaskStatus::Disabled,
             start_time: 0,
+            requests: ArrayVec::new_const(),
+            tare_tolerance: DEFAULT_TARE_TOLERANCE,
+            auto_zero: None,
+            filter_config: FilterConfig::DISABLED,
+            research_mode: false,
+            temperature_compensation: TemperatureCompensation::DISABLED,
+            auto_record: AutoRecordConfig::DISABLED,
+            recording: false,
+            rep_detection: RepDetectionConfig::DISABLED,
+            workout: None,
+            target_band: TargetBandConfig::DISABLED,
             calibration_points: [(0.0, 0.0); MAX_CALIBRATION_POINTS],
             calibration_point_count: 0,
             battery_voltage: 4300,
+            temperature: None,
+            read_diagnostics: ReadDiagnostics {
+                glitches: 0,
+                saturated: 0,
+                spikes: 0,
+            },
+            load_cell_powered_down: false,
             ble_disconnection_time: None,
         }
     }
-}
 
-impl DeviceState {
     /// Start a measurement
     pub fn start_measurement(&mut self) {
-        self.start_time = (time::Instant::now().duration_since_epoch()).as_micros() as u32;
+        self.start_time = (time::Instant::now().duration_since_epoch()).as_micros();
         self.measurement_status = MeasurementTaskStatus::Enabled;
     }
 
+    /// Start a measurement timed by the uploaded workout, if any
+    pub fn start_workout(&mut self) -> bool {
+        let Some(workout) = self.workout else {
+            return false;
+        };
+        self.start_measurement();
+        self.measurement_status = MeasurementTaskStatus::Workout(workout);
+        true
+    }
+
+    /// Start a measurement holding the peak weight
+    pub fn start_peak_hold(&mut self) {
+        self.start_measurement();
+        self.measurement_status = MeasurementTaskStatus::PeakHold;
+    }
+
+    /// Wait for the weight to reach `threshold` before starting a measurement
+    pub fn arm_measurement(&mut self, threshold: f32, pre_trigger: Duration) {
+        self.measurement_status = MeasurementTaskStatus::Armed(threshold, pre_trigger);
+    }
+
     /// Stop the current measurement
     pub fn stop_measurement(&mut self) {
         self.measurement_status = MeasurementTaskStatus::Disabled;
     }
 
-    /// Start taring process
+    /// Queue a request for the measurement task, ignoring duplicates
+    pub fn request(&mut self, request: DeviceRequest) {
+        if self.requests.contains(&request) {
+            return;
+        }
+        if self.requests.try_push(request).is_err() {
+            warn!("Too many pending requests, ignoring {:?}", request);
+        }
+    }
+
+    /// Request a tare without interrupting the current measurement
     pub fn tare(&mut self) {
-        self.measurement_status = MeasurementTaskStatus::Tare;
+        self.request(DeviceRequest::Tare);
     }
 
     /// Set calibration mode with the given weight
     pub fn calibrate(&mut self, weight: f32) {
-        self.measurement_status = MeasurementTaskStatus::Calibration(weight);
+        self.measurement_status = MeasurementTaskStatus::Calibration(weight, None);
+    }
+
+    /// Set calibration mode with the given weight, replacing the point at `index`
+    pub fn recalibrate_point(&mut self, index: usize, weight: f32) {
+        self.measurement_status = MeasurementTaskStatus::Calibration(weight, Some(index));
+    }
+
+    /// Set verification mode with the given reference weight and tolerance
+    pub fn verify_calibration(&mut self, weight: f32, tolerance: f32) {
+        self.measurement_status = MeasurementTaskStatus::Verification(weight, tolerance);
+    }
+
+    /// Abort a calibration check in progress, or else the calibration point being
+    /// collected, if any, and discard the collected points. The calibration in use is kept.
+    pub fn cancel_calibration(&mut self) {
+        match self.measurement_status {
+            MeasurementTaskStatus::Verification(..) => {
+                self.measurement_status = MeasurementTaskStatus::Disabled;
+            }
+            MeasurementTaskStatus::Calibration(..) => {
+                self.measurement_status = MeasurementTaskStatus::Disabled;
+                self.calibration_point_count = 0;
+            }
+            _ => self.calibration_point_count = 0,
+        }
+    }
+
+    /// Remove the calibration point at `index` and request a new fit
+    pub fn delete_calibration_point(&mut self, index: usize) {
+        let count = self.calibration_point_count;
+        self.calibration_points.copy_within(index + 1..count, index);
+        self.calibration_point_count -= 1;
+        self.request(DeviceRequest::ApplyCalibration);
     }
 
     pub fn get_calibration(&mut self) {
         self.measurement_status = MeasurementTaskStatus::GetCalibration;
     }
 
-    /// Reset to default calibration
+    /// Reset to the factory or default calibration
     pub fn reset_calibration(&mut self) {
         self.measurement_status = MeasurementTaskStatus::DefaultCalibration;
     }
@@ -110,14 +307,22 @@ impl DeviceState {
     /// Mark BLE as disconnected (record current time)
     pub fn on_ble_disconnected(&mut self) {
         self.ble_disconnection_time =
-            Some((time::Instant::now().duration_since_epoch()).as_millis() as u32);
+            Some((time::Instant::now().duration_since_epoch()).as_millis());
+    }
+
+    /// Mark the session recording as stopped, restarting the idle time if disconnected
+    pub fn on_recording_stopped(&mut self) {
+        self.recording = false;
+        if self.ble_disconnection_time.is_some() {
+            self.on_ble_disconnected();
+        }
     }
 
     /// Get elapsed time since BLE disconnection in milliseconds
     /// Returns None if BLE is currently connected
-    pub fn get_ble_disconnection_elapsed_ms(&self) -> Option<u32> {
+    pub fn get_ble_disconnection_elapsed_ms(&self) -> Option<u64> {
         self.ble_disconnection_time.map(|disconnect_time| {
-            let current_time = (time::Instant::now().duration_since_epoch()).as_millis() as u32;
+            let current_time = (time::Instant::now().duration_since_epoch()).as_millis();
             current_time.saturating_sub(disconnect_time)
         })
     }
@@ -145,11 +350,9 @@ pub enum ControlOpCode {
     /// Save calibration
     // TODO: Implement it
     SaveCalibration = 0x6A,
-    /// Get the error information
-    // TODO: Implement it
+    /// Get the error information, the crash log text
     GetErrorInformation = 0x6C,
     /// Clear the error information
-    // TODO: Implement it
     ClearErrorInformation = 0x6D,
     /// Turn the Progressor off (enter sleep mode)
     // TODO: Implement it
@@ -160,12 +363,108 @@ pub enum ControlOpCode {
     GetProgressorId = 0x70,
     /// Get the application version
     GetAppVersion = 0x6B,
+    /// Get the calibration curve, requested by some Tindeq app versions
+    GetCalibrationCurve = 0x71,
     /// Get the calibration values
     // Custom command, no part of Tindeq API
     GetCalibration = 0x72,
     /// Default calibration
     // Custom command, no part of Tindeq API
     DefaultCalibration = 0x74,
+    /// Set the maximum noise accepted while taring, in kg
+    // Custom command, no part of Tindeq API
+    SetTareTolerance = 0x75,
+    /// Enable or disable automatic zero tracking
+    // Custom command, no part of Tindeq API
+    SetAutoZero = 0x76,
+    /// Configure the measurement filter
+    // Custom command, no part of Tindeq API
+    SetFilter = 0x77,
+    /// Enable or disable research mode (unfiltered measurements)
+    // Custom command, no part of Tindeq API
+    SetResearchMode = 0x78,
+    /// Get the load cell glitch and outlier counters
+    // Custom command, no part of Tindeq API
+    GetDiagnostics = 0x79,
+    /// List the calibration points with their indices
+    // Custom command, no part of Tindeq API
+    ListCalibrationPoints = 0x7A,
+    /// Delete the calibration point at an index and fit the remaining points
+    // Custom command, no part of Tindeq API
+    DeleteCalibrationPoint = 0x7B,
+    /// Collect the calibration point at an index again, with a new weight, and fit the points
+    // Custom command, no part of Tindeq API
+    ReplaceCalibrationPoint = 0x7C,
+    /// Abort the calibration procedure or check, keeping the calibration in use
+    // Custom command, no part of Tindeq API
+    CancelCalibration = 0x7D,
+    /// Set the temperature compensation of the load cell drift
+    // Custom command, no part of Tindeq API
+    SetTemperatureCompensation = 0x7E,
+    /// Get the chip temperature
+    // Custom command, no part of Tindeq API
+    GetTemperature = 0x7F,
+    /// Check the calibration against a known reference weight
+    // Custom command, no part of Tindeq API
+    VerifyCalibration = 0x80,
+    /// Get the calibration history
+    // Custom command, no part of Tindeq API
+    GetCalibrationHistory = 0x81,
+    /// Save the calibration in use as the write-once factory calibration, during production.
+    /// Rejected unless built with the `factory` feature
+    // Custom command, no part of Tindeq API
+    SaveFactoryCalibration = 0x82,
+    /// Start recording a session to flash, without a client connected
+    // Custom command, no part of Tindeq API
+    StartRecording = 0x83,
+    /// Stop recording the current session
+    // Custom command, no part of Tindeq API
+    StopRecording = 0x84,
+    /// Erase the recorded sessions
+    // Custom command, no part of Tindeq API
+    ClearRecordings = 0x86,
+    /// Configure the automatic recording of pulls while no client is connected. The device
+    /// does not deep sleep while it is enabled, draining the battery much faster
+    // Custom command, no part of Tindeq API
+    SetAutoRecord = 0x87,
+    /// Configure the detection of reps and their summaries in the measurement stream
+    // Custom command, no part of Tindeq API
+    SetRepDetection = 0x88,
+    /// Start a critical force test (24 reps of 7 s work and 3 s rest), starting the
+    /// measurement
+    // Custom command, no part of Tindeq API
+    StartCriticalForceTest = 0x89,
+    /// Abort the critical force test, the measurement goes on
+    // Custom command, no part of Tindeq API
+    StopCriticalForceTest = 0x8A,
+    /// Upload an interval workout (reps, sets, work, rest and set rest times, target force)
+    // Custom command, no part of Tindeq API
+    SetWorkout = 0x8B,
+    /// Start the uploaded workout, stopped like a measurement
+    // Custom command, no part of Tindeq API
+    StartWorkout = 0x8C,
+    /// Configure the target force band events, sent alongside or instead of the
+    /// weight measurements
+    // Custom command, no part of Tindeq API
+    SetTargetBand = 0x8D,
+    /// Start a measurement sending only the new peaks of the weight, stopped like a
+    /// measurement
+    // Custom command, no part of Tindeq API
+    StartPeakHold = 0x8E,
+    /// Reset the peak held, the next sample is the new peak
+    // Custom command, no part of Tindeq API
+    ResetPeak = 0x8F,
+    /// Start measuring when the weight reaches a threshold, with timestamps starting at
+    /// that force onset and the samples preceding it sent first. Stopped like a
+    /// measurement
+    // Custom command, no part of Tindeq API
+    ArmMeasurement = 0x90,
+    /// Get the load cell fault log
+    // Custom command, no part of Tindeq API
+    GetFaultLog = 0x91,
+    /// Clear the load cell fault log
+    // Custom command, no part of Tindeq API
+    ClearFaultLog = 0x92,
 }
 
 impl ControlOpCode {
@@ -192,27 +491,15 @@ impl ControlOpCode {
                 DataPoint::from(response).send(channel);
             }
             ControlOpCode::GetProgressorId => {
-                /// Number of hex characters needed per byte (2 hex chars = 1 byte)
-                const HEX_CHARS_PER_BYTE: usize = 2;
-                /// Hex radix for parsing hex strings
-                const HEX_RADIX: u32 = 16;
-
-                let device_id = env!("DEVICE_ID");
-                let mut bytes = [0u8; DEVICE_ID_SIZE];
-                for (i, byte) in bytes.iter_mut().enumerate() {
-                    let char_pos = i * HEX_CHARS_PER_BYTE;
-                    let next_char_pos = char_pos + HEX_CHARS_PER_BYTE;
-                    if next_char_pos <= device_id.len()
-                        && let Ok(parsed_byte) =
-                            u8::from_str_radix(&device_id[char_pos..next_char_pos], HEX_RADIX)
-                    {
-                        *byte = parsed_byte;
-                    }
-                }
-                let response = ResponseCode::ProgressorId(bytes);
+                let response = ResponseCode::ProgressorId(parse_hex(env!("DEVICE_ID")));
                 info!("ProgressorId: {:?}", response);
                 DataPoint::from(response).send(channel);
             }
+            ControlOpCode::GetCalibrationCurve => {
+                let response = ResponseCode::CalibrationCurve(parse_hex(env!("CALIBRATION_CURVE")));
+                info!("CalibrationCurve: {:?}", response);
+                DataPoint::from(response).send(channel);
+            }
             ControlOpCode::GetCalibration => {
                 info!("GetCalibration requested");
                 device_state.get_calibration();
@@ -245,6 +532,380 @@ impl ControlOpCode {
             ControlOpCode::DefaultCalibration => {
                 device_state.reset_calibration();
             }
+            ControlOpCode::SetTareTolerance => {
+                let Some(tolerance) = parse_f32(data, 1) else {
+                    error!("SetTareTolerance: Invalid data length");
+                    return;
+                };
+
+                if !tolerance.is_finite() || tolerance <= 0.0 {
+                    error!("SetTareTolerance: Invalid tolerance {}", tolerance);
+                    return;
+                }
+
+                device_state.tare_tolerance = tolerance;
+                info!("Tare tolerance set to {} kg", tolerance);
+            }
+            ControlOpCode::SetAutoZero => {
+                // Payload: enable (u8), then optionally hold time in seconds (u8)
+                // and dead band in kg (f32)
+                let Some(&enable) = data.get(1) else {
+                    error!("SetAutoZero: Invalid data length");
+                    return;
+                };
+
+                if enable == 0 {
+                    device_state.auto_zero = None;
+                    return;
+                }
+
+                let mut config = AutoZeroConfig::default();
+                if let (Some(&hold_secs), Some(dead_band)) = (data.get(2), parse_f32(data, 3)) {
+                    if hold_secs == 0 || !dead_band.is_finite() || dead_band <= 0.0 {
+                        error!(
+                            "SetAutoZero: Invalid hold time {} s or dead band {} kg",
+                            hold_secs, dead_band
+                        );
+                        return;
+                    }
+                    config.hold_time = Duration::from_secs(hold_secs as u64);
+                    config.dead_band = dead_band;
+                }
+
+                info!(
+                    "Auto-zero enabled: dead band {} kg, hold time {} s",
+                    config.dead_band,
+                    config.hold_time.as_secs()
+                );
+                device_state.auto_zero = Some(config);
+            }
+            ControlOpCode::SetFilter => {
+                // Payload: median window (u8), average window (u8), low-pass factor (f32)
+                let Some(config) = data.get(1..).and_then(FilterConfig::from_bytes) else {
+                    error!("SetFilter: Invalid filter configuration");
+                    return;
+                };
+
+                info!("Filter set to {:?}", config);
+                device_state.filter_config = config;
+            }
+            ControlOpCode::SetResearchMode => {
+                let Some(&enable) = data.get(1) else {
+                    error!("SetResearchMode: Invalid data length");
+                    return;
+                };
+
+                device_state.research_mode = enable != 0;
+                info!("Research mode: {}", device_state.research_mode);
+            }
+            ControlOpCode::GetDiagnostics => {
+                let response = ResponseCode::ReadDiagnostics(device_state.read_diagnostics);
+                info!("GetDiagnostics: {:?}", response);
+                DataPoint::from(response).send(channel);
+            }
+            ControlOpCode::ListCalibrationPoints => {
+                let count = device_state.calibration_point_count;
+                let points = &device_state.calibration_points[..count];
+                info!("ListCalibrationPoints: {:?}", points);
+                if points.is_empty() {
+                    DataPoint::from(ResponseCode::CalibrationPointEntry(0, 0, 0.0, 0.0))
+                        .send(channel);
+                }
+                for (index, (raw_value, weight)) in points.iter().enumerate() {
+                    DataPoint::from(ResponseCode::CalibrationPointEntry(
+                        index as u8,
+                        count as u8,
+                        *raw_value,
+                        *weight,
+                    ))
+                    .send(channel);
+                }
+            }
+            ControlOpCode::DeleteCalibrationPoint => {
+                let Some(&index) = data.get(1) else {
+                    error!("DeleteCalibrationPoint: Invalid data length");
+                    return;
+                };
+
+                let index = index as usize;
+                if index >= device_state.calibration_point_count {
+                    error!("DeleteCalibrationPoint: Invalid index {}", index);
+                    return;
+                }
+                // The model fitted from the points would stay in use, cancel the
+                // calibration instead
+                if device_state.calibration_point_count <= MIN_CALIBRATION_POINTS {
+                    error!(
+                        "DeleteCalibrationPoint: A fit needs at least {} points",
+                        MIN_CALIBRATION_POINTS
+                    );
+                    return;
+                }
+
+                info!("Deleting calibration point {}", index);
+                device_state.delete_calibration_point(index);
+            }
+            ControlOpCode::ReplaceCalibrationPoint => {
+                // Payload: index (u8), weight in kg (f32)
+                let (Some(&index), Some(weight)) = (data.get(1), parse_f32(data, 2)) else {
+                    error!("ReplaceCalibrationPoint: Invalid data length");
+                    return;
+                };
+
+                let index = index as usize;
+                if index >= device_state.calibration_point_count {
+                    error!("ReplaceCalibrationPoint: Invalid index {}", index);
+                    return;
+                }
+                if !weight.is_finite() || weight < 0.0 {
+                    error!("ReplaceCalibrationPoint: Invalid weight {}", weight);
+                    return;
+                }
+
+                info!(
+                    "Replacing calibration point {} with weight {}",
+                    index, weight
+                );
+                device_state.recalibrate_point(index, weight);
+            }
+            ControlOpCode::VerifyCalibration => {
+                // Payload: reference weight in kg (f32), then optionally the tolerance
+                // in kg (f32), the rated precision by default
+                let Some(weight) = parse_f32(data, 1) else {
+                    error!("VerifyCalibration: Invalid data length");
+                    return;
+                };
+                let tolerance =
+                    parse_f32(data, 5).unwrap_or_else(|| calibration::precision(weight));
+
+                if !weight.is_finite() || weight < 0.0 {
+                    error!("VerifyCalibration: Invalid weight {}", weight);
+                    return;
+                }
+                if !tolerance.is_finite() || tolerance <= 0.0 {
+                    error!("VerifyCalibration: Invalid tolerance {}", tolerance);
+                    return;
+                }
+
+                info!(
+                    "Verifying calibration with {} kg, tolerance {} kg",
+                    weight, tolerance
+                );
+                device_state.verify_calibration(weight, tolerance);
+            }
+            ControlOpCode::CancelCalibration => {
+                info!("Cancelling calibration");
+                device_state.cancel_calibration();
+            }
+            ControlOpCode::SetTemperatureCompensation => {
+                // Payload: zero drift in kg/°C (f32), span drift in fraction/°C (f32)
+                let Some(compensation) =
+                    data.get(1..).and_then(TemperatureCompensation::from_bytes)
+                else {
+                    error!("SetTemperatureCompensation: Invalid compensation");
+                    return;
+                };
+
+                info!("Temperature compensation set to {:?}", compensation);
+                device_state.temperature_compensation = compensation;
+            }
+            ControlOpCode::GetTemperature => {
+                let Some(temperature) = device_state.temperature else {
+                    error!("GetTemperature: No temperature reading yet");
+                    return;
+                };
+
+                let response = ResponseCode::Temperature(temperature);
+                info!("GetTemperature: {:?}", response);
+                DataPoint::from(response).send(channel);
+            }
+            ControlOpCode::GetCalibrationHistory => {
+                device_state.request(DeviceRequest::GetCalibrationHistory);
+            }
+            ControlOpCode::SaveFactoryCalibration => {
+                // Any client could fill the write-once slot, so only production firmware
+                // accepts it
+                if !cfg!(feature = "factory") {
+                    error!("SaveFactoryCalibration: Not a factory build");
+                    DataPoint::from(ResponseCode::FactoryCalibrationSaved(false)).send(channel);
+                    return;
+                }
+
+                device_state.request(DeviceRequest::SaveFactoryCalibration);
+            }
+            ControlOpCode::StartRecording => {
+                device_state.request(DeviceRequest::StartRecording);
+            }
+            ControlOpCode::StopRecording => {
+                device_state.request(DeviceRequest::StopRecording);
+            }
+            ControlOpCode::ClearRecordings => {
+                device_state.request(DeviceRequest::ClearRecordings);
+            }
+            ControlOpCode::SetAutoRecord => {
+                // Payload: enable (u8), then optionally idle time in seconds (u8) and
+                // threshold in kg (f32)
+                let Some(&enable) = data.get(1) else {
+                    error!("SetAutoRecord: Invalid data length");
+                    return;
+                };
+
+                let mut config = AutoRecordConfig {
+                    enabled: enable != 0,
+                    ..device_state.auto_record
+                };
+                if let (Some(&idle_secs), Some(threshold)) = (data.get(2), parse_f32(data, 3)) {
+                    config.idle_time = Duration::from_secs(idle_secs as u64);
+                    config.threshold = threshold;
+                }
+                if !config.is_valid() {
+                    error!("SetAutoRecord: Invalid configuration {:?}", config);
+                    return;
+                }
+
+                info!("Automatic recording set to {:?}", config);
+                device_state.auto_record = config;
+            }
+            ControlOpCode::SetRepDetection => {
+                // Payload: enable (u8), then optionally start and end thresholds in kg
+                // (f32) and minimum duration in milliseconds (u16)
+                let Some(&enable) = data.get(1) else {
+                    error!("SetRepDetection: Invalid data length");
+                    return;
+                };
+
+                let mut config = RepDetectionConfig {
+                    enabled: enable != 0,
+                    ..device_state.rep_detection
+                };
+                if let (Some(start_threshold), Some(end_threshold), Some(min_duration)) = (
+                    parse_f32(data, 2),
+                    parse_f32(data, 6),
+                    data.get(10..12).and_then(|bytes| bytes.try_into().ok()),
+                ) {
+                    config.start_threshold = start_threshold;
+                    config.end_threshold = end_threshold;
+                    config.min_duration =
+                        Duration::from_millis(u16::from_le_bytes(min_duration) as u64);
+                }
+                if !config.is_valid() {
+                    error!("SetRepDetection: Invalid configuration {:?}", config);
+                    return;
+                }
+
+                info!("Rep detection set to {:?}", config);
+                device_state.rep_detection = config;
+            }
+            ControlOpCode::StartCriticalForceTest => {
+                device_state.start_measurement();
+                device_state.request(DeviceRequest::StartCriticalForceTest);
+            }
+            ControlOpCode::StopCriticalForceTest => {
+                device_state.request(DeviceRequest::StopCriticalForceTest);
+            }
+            ControlOpCode::SetWorkout => {
+                // Payload: reps (u8), sets (u8), work, rest and set rest times in seconds
+                // (u16) and target force in kg (f32)
+                let Some(workout) = data.get(1..).and_then(WorkoutDefinition::from_bytes) else {
+                    error!("SetWorkout: Invalid workout");
+                    return;
+                };
+
+                info!("Workout set to {:?}", workout);
+                device_state.workout = Some(workout);
+            }
+            ControlOpCode::StartWorkout => {
+                if !device_state.start_workout() {
+                    error!("StartWorkout: No workout uploaded");
+                }
+            }
+            ControlOpCode::SetTargetBand => {
+                // Payload: enable (u8), then optionally lower and upper bounds in kg (f32),
+                // minimum interval between events in milliseconds (u16) and events
+                // only (u8)
+                let Some(&enable) = data.get(1) else {
+                    error!("SetTargetBand: Invalid data length");
+                    return;
+                };
+
+                let mut config = TargetBandConfig {
+                    enabled: enable != 0,
+                    ..device_state.target_band
+                };
+                if let (Some(lower), Some(upper), Some(min_interval), Some(&events_only)) = (
+                    parse_f32(data, 2),
+                    parse_f32(data, 6),
+                    data.get(10..12).and_then(|bytes| bytes.try_into().ok()),
+                    data.get(12),
+                ) {
+                    config.lower = lower;
+                    config.upper = upper;
+                    config.min_interval =
+                        Duration::from_millis(u16::from_le_bytes(min_interval) as u64);
+                    config.events_only = events_only != 0;
+                }
+                if !config.is_valid() {
+                    error!("SetTargetBand: Invalid configuration {:?}", config);
+                    return;
+                }
+
+                info!("Target band set to {:?}", config);
+                device_state.target_band = config;
+            }
+            ControlOpCode::StartPeakHold => {
+                device_state.start_peak_hold();
+            }
+            ControlOpCode::ResetPeak => {
+                device_state.request(DeviceRequest::ResetPeak);
+            }
+            ControlOpCode::ArmMeasurement => {
+                // Payload: threshold in kg (f32), then optionally pre-trigger time in
+                // milliseconds (u16)
+                let Some(threshold) = parse_f32(data, 1) else {
+                    error!("ArmMeasurement: Invalid data length");
+                    return;
+                };
+                let pre_trigger = data
+                    .get(5..7)
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .map_or(DEFAULT_PRE_TRIGGER_TIME, |bytes| {
+                        Duration::from_millis(u16::from_le_bytes(bytes) as u64)
+                    });
+
+                if !threshold.is_finite() || threshold <= 0.0 {
+                    error!("ArmMeasurement: Invalid threshold {}", threshold);
+                    return;
+                }
+                if pre_trigger > MAX_PRE_TRIGGER_TIME {
+                    error!(
+                        "ArmMeasurement: Pre-trigger time over {} ms",
+                        MAX_PRE_TRIGGER_TIME.as_millis()
+                    );
+                    return;
+                }
+
+                info!(
+                    "Measurement armed at {} kg, pre-trigger time {} ms",
+                    threshold,
+                    pre_trigger.as_millis()
+                );
+                device_state.arm_measurement(threshold, pre_trigger);
+            }
+            ControlOpCode::GetErrorInformation => {
+                // No crash log is kept, load cell faults are read with GetFaultLog
+                let response = ResponseCode::ErrorInformation(b"");
+                info!("ErrorInformation: {}", response);
+                DataPoint::from(response).send(channel);
+            }
+            ControlOpCode::ClearErrorInformation => {
+                // No crash log to clear, load cell faults are cleared with ClearFaultLog
+            }
+            ControlOpCode::GetFaultLog => {
+                device_state.request(DeviceRequest::GetFaultLog);
+            }
+            ControlOpCode::ClearFaultLog => {
+                device_state.request(DeviceRequest::ClearFaultLog);
+            }
             ControlOpCode::SampleBattery => {
                 let voltage = device_state.battery_voltage;
                 let response = ResponseCode::SampleBatteryVoltage(voltage);
@@ -256,12 +917,36 @@ impl ControlOpCode {
             ControlOpCode::StartPeakRFDMeasurement => {}
             ControlOpCode::StartPeakRFDMeasurementSeries => {}
             ControlOpCode::SaveCalibration => {}
-            ControlOpCode::ClearErrorInformation => {}
-            ControlOpCode::GetErrorInformation => {}
         }
     }
 }
 
+/// Parse a hex string into bytes, leaving missing or invalid bytes as 0
+fn parse_hex<const N: usize>(hex: &str) -> [u8; N] {
+    /// Number of hex characters needed per byte (2 hex chars = 1 byte)
+    const HEX_CHARS_PER_BYTE: usize = 2;
+    /// Hex radix for parsing hex strings
+    const HEX_RADIX: u32 = 16;
+
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        let char_pos = i * HEX_CHARS_PER_BYTE;
+        let next_char_pos = char_pos + HEX_CHARS_PER_BYTE;
+        if next_char_pos <= hex.len()
+            && let Ok(parsed_byte) = u8::from_str_radix(&hex[char_pos..next_char_pos], HEX_RADIX)
+        {
+            *byte = parsed_byte;
+        }
+    }
+    bytes
+}
+
+/// Parse a little-endian `f32` argument starting at `offset` in the command data
+fn parse_f32(data: &[u8], offset: usize) -> Option<f32> {
+    let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(f32::from_le_bytes(bytes))
+}
+
 impl From<u8> for ControlOpCode {
     fn from(op_code: u8) -> Self {
         match op_code {
@@ -272,9 +957,39 @@ impl From<u8> for ControlOpCode {
             0x6E => ControlOpCode::Shutdown,
             0x6F => ControlOpCode::SampleBattery,
             0x70 => ControlOpCode::GetProgressorId,
+            0x71 => ControlOpCode::GetCalibrationCurve,
             0x6B => ControlOpCode::GetAppVersion,
             0x72 => ControlOpCode::GetCalibration,
             0x74 => ControlOpCode::DefaultCalibration,
+            0x75 => ControlOpCode::SetTareTolerance,
+            0x76 => ControlOpCode::SetAutoZero,
+            0x77 => ControlOpCode::SetFilter,
+            0x78 => ControlOpCode::SetResearchMode,
+            0x79 => ControlOpCode::GetDiagnostics,
+            0x7A => ControlOpCode::ListCalibrationPoints,
+            0x7B => ControlOpCode::DeleteCalibrationPoint,
+            0x7C => ControlOpCode::ReplaceCalibrationPoint,
+            0x7D => ControlOpCode::CancelCalibration,
+            0x7E => ControlOpCode::SetTemperatureCompensation,
+            0x7F => ControlOpCode::GetTemperature,
+            0x80 => ControlOpCode::VerifyCalibration,
+            0x81 => ControlOpCode::GetCalibrationHistory,
+            0x82 => ControlOpCode::SaveFactoryCalibration,
+            0x83 => ControlOpCode::StartRecording,
+            0x84 => ControlOpCode::StopRecording,
+            0x86 => ControlOpCode::ClearRecordings,
+            0x87 => ControlOpCode::SetAutoRecord,
+            0x88 => ControlOpCode::SetRepDetection,
+            0x89 => ControlOpCode::StartCriticalForceTest,
+            0x8A => ControlOpCode::StopCriticalForceTest,
+            0x8B => ControlOpCode::SetWorkout,
+            0x8C => ControlOpCode::StartWorkout,
+            0x8D => ControlOpCode::SetTargetBand,
+            0x8E => ControlOpCode::StartPeakHold,
+            0x8F => ControlOpCode::ResetPeak,
+            0x90 => ControlOpCode::ArmMeasurement,
+            0x91 => ControlOpCode::GetFaultLog,
+            0x92 => ControlOpCode::ClearFaultLog,
             0x6C => ControlOpCode::GetErrorInformation,
             0x6D => ControlOpCode::ClearErrorInformation,
             0x67 => ControlOpCode::StartPeakRFDMeasurement,
@@ -298,9 +1013,47 @@ impl Format for ControlOpCode {
             ControlOpCode::Shutdown => defmt::write!(fmt, "Shutdown"),
             ControlOpCode::SampleBattery => defmt::write!(fmt, "SampleBattery"),
             ControlOpCode::GetProgressorId => defmt::write!(fmt, "GetProgressorId"),
+            ControlOpCode::GetCalibrationCurve => defmt::write!(fmt, "GetCalibrationCurve"),
             ControlOpCode::GetCalibration => defmt::write!(fmt, "GetCalibration"),
             ControlOpCode::AddCalibrationPoint => defmt::write!(fmt, "AddCalibrationPoint"),
             ControlOpCode::DefaultCalibration => defmt::write!(fmt, "DefaultCalibration"),
+            ControlOpCode::SetTareTolerance => defmt::write!(fmt, "SetTareTolerance"),
+            ControlOpCode::SetAutoZero => defmt::write!(fmt, "SetAutoZero"),
+            ControlOpCode::SetFilter => defmt::write!(fmt, "SetFilter"),
+            ControlOpCode::SetResearchMode => defmt::write!(fmt, "SetResearchMode"),
+            ControlOpCode::GetDiagnostics => defmt::write!(fmt, "GetDiagnostics"),
+            ControlOpCode::ListCalibrationPoints => defmt::write!(fmt, "ListCalibrationPoints"),
+            ControlOpCode::DeleteCalibrationPoint => defmt::write!(fmt, "DeleteCalibrationPoint"),
+            ControlOpCode::ReplaceCalibrationPoint => {
+                defmt::write!(fmt, "ReplaceCalibrationPoint")
+            }
+            ControlOpCode::CancelCalibration => defmt::write!(fmt, "CancelCalibration"),
+            ControlOpCode::SetTemperatureCompensation => {
+                defmt::write!(fmt, "SetTemperatureCompensation")
+            }
+            ControlOpCode::GetTemperature => defmt::write!(fmt, "GetTemperature"),
+            ControlOpCode::VerifyCalibration => defmt::write!(fmt, "VerifyCalibration"),
+            ControlOpCode::GetCalibrationHistory => defmt::write!(fmt, "GetCalibrationHistory"),
+            ControlOpCode::SaveFactoryCalibration => {
+                defmt::write!(fmt, "SaveFactoryCalibration")
+            }
+            ControlOpCode::StartRecording => defmt::write!(fmt, "StartRecording"),
+            ControlOpCode::StopRecording => defmt::write!(fmt, "StopRecording"),
+            ControlOpCode::ClearRecordings => defmt::write!(fmt, "ClearRecordings"),
+            ControlOpCode::SetAutoRecord => defmt::write!(fmt, "SetAutoRecord"),
+            ControlOpCode::SetRepDetection => defmt::write!(fmt, "SetRepDetection"),
+            ControlOpCode::StartCriticalForceTest => {
+                defmt::write!(fmt, "StartCriticalForceTest")
+            }
+            ControlOpCode::StopCriticalForceTest => defmt::write!(fmt, "StopCriticalForceTest"),
+            ControlOpCode::SetWorkout => defmt::write!(fmt, "SetWorkout"),
+            ControlOpCode::StartWorkout => defmt::write!(fmt, "StartWorkout"),
+            ControlOpCode::SetTargetBand => defmt::write!(fmt, "SetTargetBand"),
+            ControlOpCode::StartPeakHold => defmt::write!(fmt, "StartPeakHold"),
+            ControlOpCode::ResetPeak => defmt::write!(fmt, "ResetPeak"),
+            ControlOpCode::ArmMeasurement => defmt::write!(fmt, "ArmMeasurement"),
+            ControlOpCode::GetFaultLog => defmt::write!(fmt, "GetFaultLog"),
+            ControlOpCode::ClearFaultLog => defmt::write!(fmt, "ClearFaultLog"),
             ControlOpCode::StartPeakRFDMeasurement => defmt::write!(fmt, "StartPeakRFDMeasurement"),
             ControlOpCode::StartPeakRFDMeasurementSeries => {
                 defmt::write!(fmt, "StartPeakRFDMeasurementSeries")
@@ -386,6 +1139,22 @@ impl DataPoint {
         }
     }
 
+    /// Send data point to the channel, waiting up to `timeout` for room
+    ///
+    /// Returns false if the channel stayed full.
+    pub async fn send_timeout(
+        &self,
+        channel: &'static DataPointChannel,
+        timeout: Duration,
+    ) -> bool {
+        if with_timeout(timeout, channel.send(*self)).await.is_err() {
+            error!("Failed to send data point: channel full");
+            return false;
+        }
+        trace!("Sent data point successfully");
+        true
+    }
+
     /// Create a weight measurement data point
     pub fn weight_measurement(weight: f32, timestamp: u32) -> Self {
         Self::from(ResponseCode::WeightMeasurement(weight, timestamp))
@@ -424,17 +1193,85 @@ pub enum ResponseCode {
     /// Response to battery voltage sampling command
     SampleBatteryVoltage(u32),
     /// Each measurement is sent together with a timestamp where the timestamp is the number of microseconds since the measurement was started
+    /// It wraps around in long sessions, see [`ResponseCode::TimestampEpoch`]
     WeightMeasurement(f32, u32),
     /// Calibration factor response
     CalibrationFactor(f32),
     /// Calibration point response (raw value, weight)
     CalibrationPoint(f32, f32),
+    /// Tare started, samples sent until [`ResponseCode::TareCompleted`] use the previous zero
+    TareStarted,
+    /// Tare completed with the given noise in kg, following samples use the new zero
+    TareCompleted(f32),
+    /// Tare timed out waiting for stable readings, with the last noise in kg. The previous zero is kept
+    TareFailed(f32),
+    /// Load cell glitch, saturation and spike counters
+    ReadDiagnostics(ReadDiagnostics),
+    /// Load cell health changed
+    SensorHealth(SensorHealth),
+    /// Fault log entry (index, entry count, health, uptime in milliseconds)
+    FaultLogEntry(u8, u8, SensorHealth, u32),
+    /// Calibration model type in use
+    CalibrationModel(ModelKind),
+    /// Calibration point fit residual (point index, fitted minus reference weight in kg)
+    CalibrationResidual(u8, f32),
+    /// Calibration quality report
+    CalibrationReport(CalibrationReport),
+    /// Calibration point with its index (index, point count, raw value, weight)
+    CalibrationPointEntry(u8, u8, f32, f32),
+    /// Chip temperature in °C
+    Temperature(f32),
+    /// Calibration check result (measured weight in kg, error in kg, passed)
+    VerificationResult(f32, f32, bool),
+    /// Calibration history entry (index, entry count, event type, uptime in milliseconds,
+    /// temperature in °C or NaN, firmware version), followed by the event details
+    CalibrationHistoryEntry(u8, u8, u8, u32, f32, [u8; FIRMWARE_VERSION_SIZE]),
+    /// Calibration history event details (index, model type, calibration factor, point count)
+    HistoryCalibration(u8, ModelKind, f32, u8),
+    /// Calibration history point (index, point index, raw value, weight)
+    HistoryCalibrationPoint(u8, u8, f32, f32),
+    /// Calibration history check details (index, reference weight, measured weight, passed)
+    HistoryVerification(u8, f32, f32, bool),
+    /// Factory calibration save result, false if it was already saved or failed
+    FactoryCalibrationSaved(bool),
+    /// Session recording started (session ID)
+    RecordingStarted(u32),
+    /// Session recording stopped (session ID, sample count)
+    RecordingStopped(u32, u32),
+    /// Summary of a completed rep
+    RepSummary(RepSummary),
+    /// Critical force test phase starting now (rep index, phase), with its duration
+    CriticalForceCue(u8, TestPhase),
+    /// Critical force test rep completed (rep index, mean force in kg)
+    CriticalForceRep(u8, f32),
+    /// Critical force test completed
+    CriticalForceResult(CriticalForceResult),
+    /// Workout phase starting now (set index, rep index, phase, phase duration)
+    WorkoutPhase(u8, u8, WorkoutPhase, Duration),
+    /// Workout rep completed (set index, rep index, mean force in kg, percentage of
+    /// samples reaching the target force)
+    WorkoutRep(u8, u8, f32, u8),
+    /// Weight moved relative to the target force band
+    TargetBand(BandPosition),
+    /// New peak weight held, with the timestamp in microseconds since the measurement
+    /// was started
+    PeakForce(f32, u32),
+    /// Samples preceding the force onset of an armed measurement, oldest first (sample
+    /// count, samples as weight and microseconds before the onset)
+    PreTriggerSamples(u8, [(f32, u32); MAX_PRE_TRIGGER_SAMPLES]),
+    /// Measurement timestamps wrapped around, the following ones are offset by the given
+    /// number of wraps of 2^32 microseconds
+    TimestampEpoch(u32),
     /// Low power warning indicating that the battery is empty. The Progressor will turn itself off after sending this warning
     LowPowerWarning,
     /// Response to app version request command
     AppVersion(&'static [u8]),
+    /// Response to error information request command, the crash log text
+    ErrorInformation(&'static [u8]),
     /// Response to progressor ID request command
     ProgressorId([u8; DEVICE_ID_SIZE]),
+    /// Response to calibration curve request command, from the `CALIBRATION_CURVE` build variable
+    CalibrationCurve([u8; CALIBRATION_CURVE_SIZE]),
     /// RFD peak response.
     // TODO: Implement it
     RfdPeak,
@@ -463,9 +1300,171 @@ impl Format for ResponseCode {
             ResponseCode::CalibrationPoint(raw, weight) => {
                 defmt::write!(fmt, "CalibrationPoint: Raw: {}, Weight: {}", raw, weight)
             }
+            ResponseCode::TareStarted => defmt::write!(fmt, "TareStarted"),
+            ResponseCode::TareCompleted(noise) => {
+                defmt::write!(fmt, "TareCompleted: Noise: {}", noise)
+            }
+            ResponseCode::TareFailed(noise) => defmt::write!(fmt, "TareFailed: Noise: {}", noise),
+            ResponseCode::ReadDiagnostics(diagnostics) => {
+                defmt::write!(fmt, "ReadDiagnostics: {}", diagnostics)
+            }
+            ResponseCode::SensorHealth(health) => defmt::write!(fmt, "SensorHealth: {}", health),
+            ResponseCode::FaultLogEntry(index, count, health, uptime_ms) => {
+                defmt::write!(
+                    fmt,
+                    "FaultLogEntry: {}/{}, Health: {}, Uptime: {} ms",
+                    index,
+                    count,
+                    health,
+                    uptime_ms
+                )
+            }
+            ResponseCode::CalibrationModel(kind) => {
+                defmt::write!(fmt, "CalibrationModel: {}", kind)
+            }
+            ResponseCode::CalibrationResidual(index, residual) => {
+                defmt::write!(fmt, "CalibrationResidual: {}: {}", index, residual)
+            }
+            ResponseCode::CalibrationReport(report) => {
+                defmt::write!(fmt, "CalibrationReport: {}", report)
+            }
+            ResponseCode::Temperature(temperature) => {
+                defmt::write!(fmt, "Temperature: {} °C", temperature)
+            }
+            ResponseCode::VerificationResult(measured, error, passed) => {
+                defmt::write!(
+                    fmt,
+                    "VerificationResult: Measured: {}, Error: {}, Passed: {}",
+                    measured,
+                    error,
+                    passed
+                )
+            }
+            ResponseCode::CalibrationPointEntry(index, count, raw, weight) => {
+                defmt::write!(
+                    fmt,
+                    "CalibrationPointEntry: {}/{}, Raw: {}, Weight: {}",
+                    index,
+                    count,
+                    raw,
+                    weight
+                )
+            }
+            ResponseCode::CalibrationHistoryEntry(
+                index,
+                count,
+                kind,
+                uptime_ms,
+                temperature,
+                version,
+            ) => {
+                defmt::write!(
+                    fmt,
+                    "CalibrationHistoryEntry: {}/{}, Type: {}, Uptime: {} ms, Temperature: {}, Version: {=[u8]:a}",
+                    index,
+                    count,
+                    kind,
+                    uptime_ms,
+                    temperature,
+                    &version[..]
+                )
+            }
+            ResponseCode::HistoryCalibration(index, kind, factor, point_count) => {
+                defmt::write!(
+                    fmt,
+                    "HistoryCalibration: {}, Model: {}, Factor: {}, Points: {}",
+                    index,
+                    kind,
+                    factor,
+                    point_count
+                )
+            }
+            ResponseCode::HistoryCalibrationPoint(index, point_index, raw, weight) => {
+                defmt::write!(
+                    fmt,
+                    "HistoryCalibrationPoint: {}, Point: {}, Raw: {}, Weight: {}",
+                    index,
+                    point_index,
+                    raw,
+                    weight
+                )
+            }
+            ResponseCode::HistoryVerification(index, reference, measured, passed) => {
+                defmt::write!(
+                    fmt,
+                    "HistoryVerification: {}, Reference: {}, Measured: {}, Passed: {}",
+                    index,
+                    reference,
+                    measured,
+                    passed
+                )
+            }
+            ResponseCode::FactoryCalibrationSaved(saved) => {
+                defmt::write!(fmt, "FactoryCalibrationSaved: {}", saved)
+            }
+            ResponseCode::RecordingStarted(session) => {
+                defmt::write!(fmt, "RecordingStarted: Session: {}", session)
+            }
+            ResponseCode::RecordingStopped(session, samples) => {
+                defmt::write!(
+                    fmt,
+                    "RecordingStopped: Session: {}, Samples: {}",
+                    session,
+                    samples
+                )
+            }
+            ResponseCode::RepSummary(summary) => defmt::write!(fmt, "RepSummary: {}", summary),
+            ResponseCode::CriticalForceCue(rep, phase) => {
+                defmt::write!(fmt, "CriticalForceCue: Rep: {}, Phase: {}", rep, phase)
+            }
+            ResponseCode::CriticalForceRep(rep, mean) => {
+                defmt::write!(fmt, "CriticalForceRep: Rep: {}, Mean: {}", rep, mean)
+            }
+            ResponseCode::CriticalForceResult(result) => {
+                defmt::write!(fmt, "CriticalForceResult: {}", result)
+            }
+            ResponseCode::WorkoutPhase(set, rep, phase, duration) => {
+                defmt::write!(
+                    fmt,
+                    "WorkoutPhase: Set: {}, Rep: {}, Phase: {}, Duration: {} ms",
+                    set,
+                    rep,
+                    phase,
+                    duration.as_millis()
+                )
+            }
+            ResponseCode::WorkoutRep(set, rep, mean, compliance) => {
+                defmt::write!(
+                    fmt,
+                    "WorkoutRep: Set: {}, Rep: {}, Mean: {}, Compliance: {}%",
+                    set,
+                    rep,
+                    mean,
+                    compliance
+                )
+            }
+            ResponseCode::TargetBand(position) => defmt::write!(fmt, "TargetBand: {}", position),
+            ResponseCode::PeakForce(weight, timestamp) => {
+                defmt::write!(
+                    fmt,
+                    "PeakForce: Weight: {}, Timestamp: {}",
+                    weight,
+                    timestamp
+                )
+            }
+            ResponseCode::PreTriggerSamples(count, samples) => {
+                defmt::write!(fmt, "PreTriggerSamples: {}", &samples[..*count as usize])
+            }
+            ResponseCode::TimestampEpoch(epoch) => defmt::write!(fmt, "TimestampEpoch: {}", epoch),
             ResponseCode::LowPowerWarning => defmt::write!(fmt, "LowPowerWarning"),
             ResponseCode::AppVersion(version) => defmt::write!(fmt, "AppVersion: {:x}", version),
+            ResponseCode::ErrorInformation(text) => {
+                defmt::write!(fmt, "ErrorInformation: {:x}", text)
+            }
             ResponseCode::ProgressorId(id) => defmt::write!(fmt, "ProgressorId: {:x}", id),
+            ResponseCode::CalibrationCurve(curve) => {
+                defmt::write!(fmt, "CalibrationCurve: {:x}", curve)
+            }
             ResponseCode::RfdPeak => defmt::write!(fmt, "RfdPeak"),
             ResponseCode::RfdPeakSeries => defmt::write!(fmt, "RfdPeakSeries"),
         }
@@ -478,13 +1477,44 @@ impl ResponseCode {
         match self {
             ResponseCode::SampleBatteryVoltage(..)
             | ResponseCode::AppVersion(..)
-            | ResponseCode::ProgressorId(..) => 0x00,
+            | ResponseCode::ErrorInformation(..)
+            | ResponseCode::ProgressorId(..)
+            | ResponseCode::CalibrationCurve(..) => 0x00,
             ResponseCode::WeightMeasurement(..) => 0x01,
             ResponseCode::RfdPeak => 0x02,
             ResponseCode::RfdPeakSeries => 0x03,
             ResponseCode::LowPowerWarning => 0x04,
             ResponseCode::CalibrationFactor(..) => 0x05,
             ResponseCode::CalibrationPoint(..) => 0x06,
+            ResponseCode::TareStarted => 0x07,
+            ResponseCode::TareCompleted(..) => 0x08,
+            ResponseCode::TareFailed(..) => 0x09,
+            ResponseCode::ReadDiagnostics(..) => 0x0A,
+            ResponseCode::SensorHealth(..) => 0x0B,
+            ResponseCode::FaultLogEntry(..) => 0x0C,
+            ResponseCode::CalibrationModel(..) => 0x0D,
+            ResponseCode::CalibrationResidual(..) => 0x0E,
+            ResponseCode::CalibrationReport(..) => 0x0F,
+            ResponseCode::CalibrationPointEntry(..) => 0x10,
+            ResponseCode::Temperature(..) => 0x11,
+            ResponseCode::VerificationResult(..) => 0x12,
+            ResponseCode::CalibrationHistoryEntry(..) => 0x13,
+            ResponseCode::HistoryCalibration(..) => 0x14,
+            ResponseCode::HistoryCalibrationPoint(..) => 0x15,
+            ResponseCode::HistoryVerification(..) => 0x16,
+            ResponseCode::FactoryCalibrationSaved(..) => 0x17,
+            ResponseCode::RecordingStarted(..) => 0x18,
+            ResponseCode::RecordingStopped(..) => 0x19,
+            ResponseCode::RepSummary(..) => 0x1D,
+            ResponseCode::CriticalForceCue(..) => 0x1E,
+            ResponseCode::CriticalForceRep(..) => 0x1F,
+            ResponseCode::CriticalForceResult(..) => 0x20,
+            ResponseCode::WorkoutPhase(..) => 0x21,
+            ResponseCode::WorkoutRep(..) => 0x22,
+            ResponseCode::TargetBand(..) => 0x23,
+            ResponseCode::PeakForce(..) => 0x24,
+            ResponseCode::PreTriggerSamples(..) => 0x25,
+            ResponseCode::TimestampEpoch(..) => 0x26,
         }
     }
 
@@ -495,9 +1525,40 @@ impl ResponseCode {
             ResponseCode::WeightMeasurement(..) => 8,
             ResponseCode::CalibrationFactor(..) => 4,
             ResponseCode::CalibrationPoint(..) => 8,
+            ResponseCode::TareStarted => 0,
+            ResponseCode::TareCompleted(..) | ResponseCode::TareFailed(..) => 4,
+            ResponseCode::ReadDiagnostics(..) => 12,
+            ResponseCode::SensorHealth(..) => 1,
+            ResponseCode::FaultLogEntry(..) => 7,
+            ResponseCode::CalibrationModel(..) => 1,
+            ResponseCode::CalibrationResidual(..) => 5,
+            ResponseCode::CalibrationReport(..) => 14,
+            ResponseCode::CalibrationPointEntry(..) => 10,
+            ResponseCode::Temperature(..) => 4,
+            ResponseCode::VerificationResult(..) => 9,
+            ResponseCode::CalibrationHistoryEntry(..) => 11 + FIRMWARE_VERSION_SIZE as u8,
+            ResponseCode::HistoryCalibration(..) => 7,
+            ResponseCode::HistoryCalibrationPoint(..) => 10,
+            ResponseCode::HistoryVerification(..) => 10,
+            ResponseCode::FactoryCalibrationSaved(..) => 1,
+            ResponseCode::RecordingStarted(..) => 4,
+            ResponseCode::RecordingStopped(..) => 8,
+            ResponseCode::RepSummary(..) => 18,
+            ResponseCode::CriticalForceCue(..) => 4,
+            ResponseCode::CriticalForceRep(..) => 5,
+            ResponseCode::CriticalForceResult(..) => 8,
+            ResponseCode::WorkoutPhase(..) => 7,
+            ResponseCode::WorkoutRep(..) => 7,
+            ResponseCode::TargetBand(..) => 1,
+            ResponseCode::PeakForce(..) => 8,
+            ResponseCode::PreTriggerSamples(count, _) => 1 + 8 * *count,
+            ResponseCode::TimestampEpoch(..) => 4,
             ResponseCode::LowPowerWarning => 0,
-            ResponseCode::AppVersion(version) => version.len().min(MAX_PAYLOAD_SIZE) as u8,
+            ResponseCode::AppVersion(version) | ResponseCode::ErrorInformation(version) => {
+                version.len().min(MAX_PAYLOAD_SIZE) as u8
+            }
             ResponseCode::ProgressorId(..) => DEVICE_ID_SIZE as u8,
+            ResponseCode::CalibrationCurve(..) => CALIBRATION_CURVE_SIZE as u8,
             ResponseCode::RfdPeak => 0,
             ResponseCode::RfdPeakSeries => 0,
         }
@@ -510,7 +1571,8 @@ impl ResponseCode {
             ResponseCode::SampleBatteryVoltage(voltage) => {
                 value[0..4].copy_from_slice(&voltage.to_le_bytes());
             }
-            ResponseCode::WeightMeasurement(weight, timestamp) => {
+            ResponseCode::WeightMeasurement(weight, timestamp)
+            | ResponseCode::PeakForce(weight, timestamp) => {
                 value[0..4].copy_from_slice(&weight.to_le_bytes());
                 value[4..8].copy_from_slice(&timestamp.to_le_bytes());
             }
@@ -521,6 +1583,145 @@ impl ResponseCode {
                 value[0..4].copy_from_slice(&raw_value.to_le_bytes());
                 value[4..8].copy_from_slice(&weight.to_le_bytes());
             }
+            ResponseCode::TareStarted => (),
+            ResponseCode::TareCompleted(noise) | ResponseCode::TareFailed(noise) => {
+                value[0..4].copy_from_slice(&noise.to_le_bytes());
+            }
+            ResponseCode::ReadDiagnostics(diagnostics) => {
+                value[0..4].copy_from_slice(&diagnostics.glitches.to_le_bytes());
+                value[4..8].copy_from_slice(&diagnostics.saturated.to_le_bytes());
+                value[8..12].copy_from_slice(&diagnostics.spikes.to_le_bytes());
+            }
+            ResponseCode::SensorHealth(health) => {
+                value[0] = *health as u8;
+            }
+            ResponseCode::FaultLogEntry(index, count, health, uptime_ms) => {
+                value[0] = *index;
+                value[1] = *count;
+                value[2] = *health as u8;
+                value[3..7].copy_from_slice(&uptime_ms.to_le_bytes());
+            }
+            ResponseCode::CalibrationModel(kind) => {
+                value[0] = *kind as u8;
+            }
+            ResponseCode::CalibrationResidual(index, residual) => {
+                value[0] = *index;
+                value[1..5].copy_from_slice(&residual.to_le_bytes());
+            }
+            ResponseCode::CalibrationReport(report) => {
+                value[0..4].copy_from_slice(&report.r_squared.to_le_bytes());
+                value[4..8].copy_from_slice(&report.max_error.to_le_bytes());
+                value[8] = report.warnings;
+                value[9] = report.passed as u8;
+                value[10..14].copy_from_slice(&report.outliers.to_le_bytes());
+            }
+            ResponseCode::Temperature(temperature) => {
+                value[0..4].copy_from_slice(&temperature.to_le_bytes());
+            }
+            ResponseCode::VerificationResult(measured, error, passed) => {
+                value[0..4].copy_from_slice(&measured.to_le_bytes());
+                value[4..8].copy_from_slice(&error.to_le_bytes());
+                value[8] = *passed as u8;
+            }
+            ResponseCode::CalibrationPointEntry(index, count, raw_value, weight) => {
+                value[0] = *index;
+                value[1] = *count;
+                value[2..6].copy_from_slice(&raw_value.to_le_bytes());
+                value[6..10].copy_from_slice(&weight.to_le_bytes());
+            }
+            ResponseCode::CalibrationHistoryEntry(
+                index,
+                count,
+                kind,
+                uptime_ms,
+                temperature,
+                version,
+            ) => {
+                value[0] = *index;
+                value[1] = *count;
+                value[2] = *kind;
+                value[3..7].copy_from_slice(&uptime_ms.to_le_bytes());
+                value[7..11].copy_from_slice(&temperature.to_le_bytes());
+                value[11..11 + FIRMWARE_VERSION_SIZE].copy_from_slice(version);
+            }
+            ResponseCode::HistoryCalibration(index, kind, factor, point_count) => {
+                value[0] = *index;
+                value[1] = *kind as u8;
+                value[2..6].copy_from_slice(&factor.to_le_bytes());
+                value[6] = *point_count;
+            }
+            ResponseCode::HistoryCalibrationPoint(index, point_index, raw_value, weight) => {
+                value[0] = *index;
+                value[1] = *point_index;
+                value[2..6].copy_from_slice(&raw_value.to_le_bytes());
+                value[6..10].copy_from_slice(&weight.to_le_bytes());
+            }
+            ResponseCode::HistoryVerification(index, reference, measured, passed) => {
+                value[0] = *index;
+                value[1..5].copy_from_slice(&reference.to_le_bytes());
+                value[5..9].copy_from_slice(&measured.to_le_bytes());
+                value[9] = *passed as u8;
+            }
+            ResponseCode::FactoryCalibrationSaved(saved) => {
+                value[0] = *saved as u8;
+            }
+            ResponseCode::RecordingStarted(session) => {
+                value[0..4].copy_from_slice(&session.to_le_bytes());
+            }
+            ResponseCode::RecordingStopped(session, samples) => {
+                value[0..4].copy_from_slice(&session.to_le_bytes());
+                value[4..8].copy_from_slice(&samples.to_le_bytes());
+            }
+            ResponseCode::RepSummary(summary) => {
+                value[0..4].copy_from_slice(&summary.peak.to_le_bytes());
+                value[4..8].copy_from_slice(&summary.average.to_le_bytes());
+                value[8..12].copy_from_slice(&summary.impulse.to_le_bytes());
+                value[12..16].copy_from_slice(&summary.time_under_tension_ms.to_le_bytes());
+                // Time to peak in milliseconds, saturating at u16::MAX
+                let time_to_peak_ms = summary.time_to_peak_ms.min(u16::MAX as u32) as u16;
+                value[16..18].copy_from_slice(&time_to_peak_ms.to_le_bytes());
+            }
+            ResponseCode::CriticalForceCue(rep, phase) => {
+                value[0] = *rep;
+                value[1] = *phase as u8;
+                // Phase duration in milliseconds
+                let duration_ms = phase.duration().as_millis() as u16;
+                value[2..4].copy_from_slice(&duration_ms.to_le_bytes());
+            }
+            ResponseCode::CriticalForceRep(rep, mean) => {
+                value[0] = *rep;
+                value[1..5].copy_from_slice(&mean.to_le_bytes());
+            }
+            ResponseCode::CriticalForceResult(result) => {
+                value[0..4].copy_from_slice(&result.critical_force.to_le_bytes());
+                value[4..8].copy_from_slice(&result.w_prime.to_le_bytes());
+            }
+            ResponseCode::WorkoutPhase(set, rep, phase, duration) => {
+                value[0] = *set;
+                value[1] = *rep;
+                value[2] = *phase as u8;
+                value[3..7].copy_from_slice(&(duration.as_millis() as u32).to_le_bytes());
+            }
+            ResponseCode::WorkoutRep(set, rep, mean, compliance) => {
+                value[0] = *set;
+                value[1] = *rep;
+                value[2..6].copy_from_slice(&mean.to_le_bytes());
+                value[6] = *compliance;
+            }
+            ResponseCode::TargetBand(position) => {
+                value[0] = *position as u8;
+            }
+            ResponseCode::PreTriggerSamples(count, samples) => {
+                value[0] = *count;
+                for (i, (weight, before)) in samples[..*count as usize].iter().enumerate() {
+                    let offset = 1 + 8 * i;
+                    value[offset..offset + 4].copy_from_slice(&weight.to_le_bytes());
+                    value[offset + 4..offset + 8].copy_from_slice(&before.to_le_bytes());
+                }
+            }
+            ResponseCode::TimestampEpoch(epoch) => {
+                value[0..4].copy_from_slice(&epoch.to_le_bytes());
+            }
             ResponseCode::LowPowerWarning => (),
             ResponseCode::ProgressorId(id) => {
                 // Reverse the bytes as they are LE
@@ -528,7 +1729,10 @@ impl ResponseCode {
                 reversed.reverse();
                 value[..DEVICE_ID_SIZE].copy_from_slice(&reversed);
             }
-            ResponseCode::AppVersion(version) => {
+            ResponseCode::CalibrationCurve(curve) => {
+                value[..CALIBRATION_CURVE_SIZE].copy_from_slice(curve);
+            }
+            ResponseCode::AppVersion(version) | ResponseCode::ErrorInformation(version) => {
                 let len = version.len().min(MAX_PAYLOAD_SIZE);
                 value[0..len].copy_from_slice(&version[0..len]);
             }
@@ -0,0 +1,434 @@
+/// Offline session recording
+///
+/// Timestamped weight samples are stored in a ring of flash sectors in the sessions
+/// partition, so training without a connected phone can be downloaded later with the
+/// bulk transfer, see [`crate::transfer`]. Each sector starts with a header and is
+/// filled with fixed-size records in order; once the ring is full, the oldest sector is
+/// erased and reused.
+///
+/// Records are buffered in RAM and programmed a flash page at a time, so the flash is
+/// not busy on every sample. A power loss drops the buffered records, up to a page.
+use defmt::{Format, error, info};
+use embassy_time::{Duration, Instant};
+
+use crate::storage::{self, SECTOR_SIZE, SESSIONS_ADDR, SESSIONS_SIZE, StorageError};
+
+/// Number of sectors in the ring
+const SECTOR_COUNT: u32 = SESSIONS_SIZE / SECTOR_SIZE;
+/// Size of a sector header: sequence number and next session ID (`u32`, little-endian)
+const HEADER_SIZE: u32 = 8;
+/// Size of a record: timestamp or marker (`u32`) and value (`u32` or `f32`), little-endian
+pub const RECORD_SIZE: usize = 8;
+/// Number of records in a sector
+const RECORDS_PER_SECTOR: u32 = (SECTOR_SIZE - HEADER_SIZE) / RECORD_SIZE as u32;
+/// Size of a flash page, records are programmed a page at a time
+const PAGE_SIZE: u32 = 256;
+/// Erased word, marks an unused sector or record
+const ERASED: u32 = u32::MAX;
+/// Marker of a session start record, followed by the session ID
+const SESSION_START: u32 = u32::MAX - 1;
+
+/// Automatic recording configuration
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct AutoRecordConfig {
+    /// Start recording on a pull while no client is connected
+    pub enabled: bool,
+    /// Weight starting a recording, in kg
+    pub threshold: f32,
+    /// Time below the threshold ending an automatic recording
+    pub idle_time: Duration,
+}
+
+impl Default for AutoRecordConfig {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl AutoRecordConfig {
+    /// Configuration never starting a recording on its own
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        threshold: 2.0,
+        idle_time: Duration::from_secs(30),
+    };
+
+    /// Size of the serialized configuration in bytes
+    pub const SIZE: usize = 6;
+
+    /// Check if the configuration is within the supported ranges
+    pub fn is_valid(&self) -> bool {
+        self.threshold.is_finite() && self.threshold > 0.0 && self.idle_time.as_secs() > 0
+    }
+
+    /// Serialize as enabled flag, idle time in seconds and little-endian threshold
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = self.enabled as u8;
+        bytes[1] = self.idle_time.as_secs().min(u8::MAX as u64) as u8;
+        bytes[2..6].copy_from_slice(&self.threshold.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a configuration, returning None if it is invalid
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let config = Self {
+            enabled: *bytes.first()? == 1,
+            idle_time: Duration::from_secs(*bytes.get(1)? as u64),
+            threshold: f32::from_le_bytes(bytes.get(2..6)?.try_into().ok()?),
+        };
+        config.is_valid().then_some(config)
+    }
+}
+
+/// Recorded weight sample
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct RecordedSample {
+    /// Time since the session started, in milliseconds
+    pub timestamp_ms: u32,
+    /// Weight in kg
+    pub weight: f32,
+}
+
+/// Stored record
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub enum Record {
+    /// Start of a session, with its ID
+    SessionStart(u32),
+    /// Weight sample of the last started session
+    Sample(RecordedSample),
+}
+
+impl Record {
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let (marker, value) = match self {
+            Record::SessionStart(id) => (SESSION_START, id.to_le_bytes()),
+            Record::Sample(sample) => (sample.timestamp_ms, sample.weight.to_le_bytes()),
+        };
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&marker.to_le_bytes());
+        bytes[4..8].copy_from_slice(&value);
+        bytes
+    }
+
+    /// Deserialize a record, returning None for an unused or interrupted one
+    fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let marker = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let value = [bytes[4], bytes[5], bytes[6], bytes[7]];
+        match marker {
+            ERASED => None,
+            SESSION_START => {
+                let id = u32::from_le_bytes(value);
+                (id != ERASED).then_some(Record::SessionStart(id))
+            }
+            timestamp_ms => {
+                let weight = f32::from_le_bytes(value);
+                weight.is_finite().then_some(Record::Sample(RecordedSample {
+                    timestamp_ms,
+                    weight,
+                }))
+            }
+        }
+    }
+}
+
+/// Session being recorded
+#[derive(Clone, Copy, Debug)]
+struct Session {
+    /// Session ID
+    id: u32,
+    /// Time the session started
+    start: Instant,
+    /// Number of recorded samples
+    samples: u32,
+}
+
+/// Sector header
+#[derive(Clone, Copy, Debug)]
+struct SectorHeader {
+    /// Increases with every sector written, orders the ring
+    sequence: u32,
+    /// Next session ID when the sector was started
+    next_session_id: u32,
+}
+
+fn sector_address(sector: u32) -> u32 {
+    SESSIONS_ADDR + sector * SECTOR_SIZE
+}
+
+fn record_address(sector: u32, index: u32) -> u32 {
+    sector_address(sector) + HEADER_SIZE + index * RECORD_SIZE as u32
+}
+
+fn read_header(sector: u32) -> Result<Option<SectorHeader>, StorageError> {
+    let mut bytes = [0u8; HEADER_SIZE as usize];
+    storage::read(sector_address(sector), &mut bytes)?;
+    let sequence = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let next_session_id = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    Ok((sequence != ERASED).then_some(SectorHeader {
+        sequence,
+        next_session_id,
+    }))
+}
+
+fn read_record_bytes(sector: u32, index: u32) -> Result<[u8; RECORD_SIZE], StorageError> {
+    let mut bytes = [0u8; RECORD_SIZE];
+    storage::read(record_address(sector, index), &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Index of the first free record in `sector` and the ID of its last session start
+fn scan_sector(sector: u32) -> Result<(u32, Option<u32>), StorageError> {
+    let mut last_session_id = None;
+    for index in 0..RECORDS_PER_SECTOR {
+        let bytes = read_record_bytes(sector, index)?;
+        if bytes == [0xFF; RECORD_SIZE] {
+            return Ok((index, last_session_id));
+        }
+        if let Some(Record::SessionStart(id)) = Record::from_bytes(&bytes) {
+            last_session_id = Some(id);
+        }
+    }
+    Ok((RECORDS_PER_SECTOR, last_session_id))
+}
+
+/// Oldest and newest used sectors, with the header of the newest one
+fn find_sectors() -> Result<Option<(u32, u32, SectorHeader)>, StorageError> {
+    let mut newest: Option<(u32, SectorHeader)> = None;
+    for sector in 0..SECTOR_COUNT {
+        if let Some(header) = read_header(sector)?
+            && newest.is_none_or(|(_, newest)| header.sequence > newest.sequence)
+        {
+            newest = Some((sector, header));
+        }
+    }
+    let Some((newest, header)) = newest else {
+        return Ok(None);
+    };
+
+    // Sectors are used in order, so the oldest follows the newest once the ring wrapped
+    let next = (newest + 1) % SECTOR_COUNT;
+    let oldest = if read_header(next)?.is_some() {
+        next
+    } else {
+        0
+    };
+    Ok(Some((oldest, newest, header)))
+}
+
+/// Recorder of sessions into the flash ring
+#[derive(Debug)]
+pub struct SessionRecorder {
+    /// Sector being written
+    sector: u32,
+    /// Sequence number of the sector being written
+    sequence: u32,
+    /// Index of the next free record in the sector, counting the buffered ones
+    next_record: u32,
+    /// Records not programmed yet, all in the page of the last one
+    buffer: [u8; PAGE_SIZE as usize],
+    /// Number of buffered records
+    buffered: u32,
+    /// ID of the next session
+    next_session_id: u32,
+    /// Session being recorded, if any
+    session: Option<Session>,
+}
+
+impl SessionRecorder {
+    /// Find where the last recording stopped, starting an empty ring if there is none
+    pub fn open() -> Result<Self, StorageError> {
+        let Some((_, sector, header)) = find_sectors()? else {
+            let mut recorder = Self {
+                sector: 0,
+                sequence: 0,
+                next_record: 0,
+                buffer: [0xFF; PAGE_SIZE as usize],
+                buffered: 0,
+                next_session_id: 0,
+                session: None,
+            };
+            recorder.start_sector(0, 0)?;
+            return Ok(recorder);
+        };
+
+        let (next_record, last_session_id) = scan_sector(sector)?;
+        let recorder = Self {
+            sector,
+            sequence: header.sequence,
+            next_record,
+            buffer: [0xFF; PAGE_SIZE as usize],
+            buffered: 0,
+            next_session_id: last_session_id
+                .map_or(header.next_session_id, |id| id.wrapping_add(1)),
+            session: None,
+        };
+        info!(
+            "Session recorder opened at sector {}, record {}, next session {}",
+            recorder.sector, recorder.next_record, recorder.next_session_id
+        );
+        Ok(recorder)
+    }
+
+    /// Erase `sector` and write its header
+    fn start_sector(&mut self, sector: u32, sequence: u32) -> Result<(), StorageError> {
+        storage::erase(sector_address(sector), SECTOR_SIZE)?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(&sequence.to_le_bytes());
+        header[4..8].copy_from_slice(&self.next_session_id.to_le_bytes());
+        storage::program(sector_address(sector), &header)?;
+        self.sector = sector;
+        self.sequence = sequence;
+        self.next_record = 0;
+        Ok(())
+    }
+
+    /// Append a record, moving on to the next sector when the current one is full
+    ///
+    /// The record is buffered, and programmed once it completes a page or the sector.
+    fn append(&mut self, record: Record) -> Result<(), StorageError> {
+        if self.next_record >= RECORDS_PER_SECTOR {
+            // Overwrites the oldest sector once the ring is full
+            self.start_sector((self.sector + 1) % SECTOR_COUNT, self.sequence + 1)?;
+        }
+        let offset = (record_address(self.sector, self.next_record) % PAGE_SIZE) as usize;
+        self.buffer[offset..offset + RECORD_SIZE].copy_from_slice(&record.to_bytes());
+        self.buffered += 1;
+        self.next_record += 1;
+
+        let page_end = record_address(self.sector, self.next_record).is_multiple_of(PAGE_SIZE);
+        if page_end || self.next_record >= RECORDS_PER_SECTOR {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Program the buffered records
+    fn flush(&mut self) -> Result<(), StorageError> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        let first = self.next_record - self.buffered;
+        let address = record_address(self.sector, first);
+        let offset = (address % PAGE_SIZE) as usize;
+        let len = self.buffered as usize * RECORD_SIZE;
+        // Buffered records are dropped on failure, the next ones go after them
+        self.buffered = 0;
+        storage::program(address, &self.buffer[offset..offset + len])
+    }
+
+    /// Check if a session is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Start a new session, returning its ID. A session in progress is stopped first.
+    pub fn start(&mut self) -> Result<u32, StorageError> {
+        self.stop();
+        let id = self.next_session_id;
+        self.append(Record::SessionStart(id))?;
+        self.next_session_id = id.wrapping_add(1);
+        self.session = Some(Session {
+            id,
+            start: Instant::now(),
+            samples: 0,
+        });
+        info!("Recording session {}", id);
+        Ok(id)
+    }
+
+    /// Record a weight sample in the current session
+    pub fn record(&mut self, weight: f32) -> Result<(), StorageError> {
+        let Some(session) = self.session else {
+            return Ok(());
+        };
+        let timestamp_ms = session
+            .start
+            .elapsed()
+            .as_millis()
+            .min(SESSION_START as u64 - 1);
+        self.append(Record::Sample(RecordedSample {
+            timestamp_ms: timestamp_ms as u32,
+            weight,
+        }))?;
+        if let Some(session) = &mut self.session {
+            session.samples += 1;
+        }
+        Ok(())
+    }
+
+    /// Stop the current session, returning its ID and sample count
+    ///
+    /// The buffered records are programmed.
+    pub fn stop(&mut self) -> Option<(u32, u32)> {
+        let session = self.session.take()?;
+        if let Err(e) = self.flush() {
+            error!(
+                "Failed to write recorded samples: {:?}",
+                defmt::Debug2Format(&e)
+            );
+        }
+        info!(
+            "Recording session {} stopped after {} samples",
+            session.id, session.samples
+        );
+        Some((session.id, session.samples))
+    }
+
+    /// Erase all recorded sessions, stopping the current one
+    ///
+    /// Sectors are erased one at a time, yielding in between.
+    pub async fn clear(&mut self) -> Result<(), StorageError> {
+        self.stop();
+        for sector in 0..SECTOR_COUNT {
+            if read_header(sector)?.is_some() {
+                storage::erase(sector_address(sector), SECTOR_SIZE)?;
+                embassy_futures::yield_now().await;
+            }
+        }
+        // Keep counting session IDs so old downloads are not confused with new ones
+        self.start_sector(0, 0)
+    }
+}
+
+/// Reader of the stored records, from the oldest up to the last written one
+#[derive(Clone, Copy, Debug)]
+pub struct RecordReader {
+    /// Sector of the oldest record
+    oldest: u32,
+    /// Number of records
+    len: u32,
+}
+
+impl RecordReader {
+    fn new(oldest: u32, end_sector: u32, end_record: u32) -> Self {
+        let sectors = (end_sector + SECTOR_COUNT - oldest) % SECTOR_COUNT;
+        Self {
+            oldest,
+            len: sectors * RECORDS_PER_SECTOR + end_record,
+        }
+    }
+
+    /// Reader of the records stored at the time of the call
+    pub fn open() -> Result<Self, StorageError> {
+        let Some((oldest, newest, _)) = find_sectors()? else {
+            return Ok(Self::new(0, 0, 0));
+        };
+        let (end_record, _) = scan_sector(newest)?;
+        Ok(Self::new(oldest, newest, end_record))
+    }
+
+    /// Number of records, including interrupted ones
+    pub fn record_count(&self) -> u32 {
+        self.len
+    }
+
+    /// Read the serialized record at `index`, 0 being the oldest
+    pub fn read_raw(&self, index: u32) -> Result<Option<[u8; RECORD_SIZE]>, StorageError> {
+        if index >= self.len {
+            return Ok(None);
+        }
+        let sector = (self.oldest + index / RECORDS_PER_SECTOR) % SECTOR_COUNT;
+        read_record_bytes(sector, index % RECORDS_PER_SECTOR).map(Some)
+    }
+}
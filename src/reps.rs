@@ -0,0 +1,267 @@
+/// Repetition detection
+///
+/// Segments the measurement stream into repetitions (pulls) with two thresholds: a rep
+/// starts when the weight reaches the start threshold and ends when it drops below the
+/// lower end threshold, so noise around a single threshold does not split a pull. Each
+/// completed rep is summarized with the numbers usually written down by hand.
+use defmt::Format;
+use embassy_time::{Duration, Instant};
+
+/// Repetition detection configuration
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct RepDetectionConfig {
+    /// Detect reps in the measurement stream
+    pub enabled: bool,
+    /// Weight starting a rep, in kg
+    pub start_threshold: f32,
+    /// Weight ending a rep, in kg, below the start threshold for hysteresis
+    pub end_threshold: f32,
+    /// Shortest rep reported, shorter ones are ignored as bumps
+    pub min_duration: Duration,
+}
+
+impl Default for RepDetectionConfig {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl RepDetectionConfig {
+    /// Configuration not detecting any reps
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        start_threshold: 3.0,
+        end_threshold: 1.5,
+        min_duration: Duration::from_millis(300),
+    };
+
+    /// Size of the serialized configuration in bytes
+    pub const SIZE: usize = 11;
+
+    /// Check if the configuration is within the supported ranges
+    pub fn is_valid(&self) -> bool {
+        self.start_threshold.is_finite()
+            && self.end_threshold.is_finite()
+            && self.end_threshold >= 0.0
+            && self.end_threshold < self.start_threshold
+            && self.min_duration.as_millis() <= u16::MAX as u64
+    }
+
+    /// Serialize as enabled flag, little-endian start and end thresholds and minimum
+    /// duration in milliseconds (`u16`)
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = self.enabled as u8;
+        bytes[1..5].copy_from_slice(&self.start_threshold.to_le_bytes());
+        bytes[5..9].copy_from_slice(&self.end_threshold.to_le_bytes());
+        bytes[9..11].copy_from_slice(&(self.min_duration.as_millis() as u16).to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a configuration, returning None if it is invalid
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let config = Self {
+            enabled: *bytes.first()? == 1,
+            start_threshold: f32::from_le_bytes(bytes.get(1..5)?.try_into().ok()?),
+            end_threshold: f32::from_le_bytes(bytes.get(5..9)?.try_into().ok()?),
+            min_duration: Duration::from_millis(u16::from_le_bytes(
+                bytes.get(9..11)?.try_into().ok()?,
+            ) as u64),
+        };
+        config.is_valid().then_some(config)
+    }
+}
+
+/// Summary of a completed rep
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct RepSummary {
+    /// Highest weight, in kg
+    pub peak: f32,
+    /// Mean weight over the time under tension, in kg
+    pub average: f32,
+    /// Weight integrated over time, in kg·s
+    pub impulse: f32,
+    /// Time from the rep start to its end, in milliseconds
+    pub time_under_tension_ms: u32,
+    /// Time from the rep start to the peak, in milliseconds
+    pub time_to_peak_ms: u32,
+}
+
+/// Rep in progress
+#[derive(Clone, Copy, Debug)]
+struct Rep {
+    start: Instant,
+    last: Instant,
+    last_weight: f32,
+    peak: f32,
+    peak_time: Instant,
+    impulse: f32,
+}
+
+impl Rep {
+    fn summary(&self) -> RepSummary {
+        let duration = self.last - self.start;
+        let seconds = duration.as_micros() as f32 / 1_000_000.0;
+        RepSummary {
+            peak: self.peak,
+            average: if seconds > 0.0 {
+                self.impulse / seconds
+            } else {
+                self.peak
+            },
+            impulse: self.impulse,
+            time_under_tension_ms: duration.as_millis() as u32,
+            time_to_peak_ms: (self.peak_time - self.start).as_millis() as u32,
+        }
+    }
+}
+
+/// Rep detection state
+#[derive(Clone, Copy, Debug)]
+pub struct RepDetector {
+    config: RepDetectionConfig,
+    rep: Option<Rep>,
+}
+
+impl RepDetector {
+    /// Create a rep detector with the given configuration
+    pub fn new(config: RepDetectionConfig) -> Self {
+        Self { config, rep: None }
+    }
+
+    /// Get the rep detection configuration
+    pub fn config(&self) -> RepDetectionConfig {
+        self.config
+    }
+
+    /// Discard the rep in progress, e.g. when a new measurement starts
+    pub fn reset(&mut self) {
+        self.rep = None;
+    }
+
+    /// Add a sample taken at `time`, returning the summary of the rep it completes
+    pub fn update(&mut self, weight: f32, time: Instant) -> Option<RepSummary> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let Some(rep) = &mut self.rep else {
+            if weight >= self.config.start_threshold {
+                self.rep = Some(Rep {
+                    start: time,
+                    last: time,
+                    last_weight: weight,
+                    peak: weight,
+                    peak_time: time,
+                    impulse: 0.0,
+                });
+            }
+            return None;
+        };
+
+        if weight < self.config.end_threshold {
+            let rep = self.rep.take()?;
+            return (rep.last - rep.start >= self.config.min_duration).then(|| rep.summary());
+        }
+
+        // Trapezoidal integration between consecutive samples
+        let dt = (time - rep.last).as_micros() as f32 / 1_000_000.0;
+        rep.impulse += (rep.last_weight + weight) / 2.0 * dt;
+        rep.last = time;
+        rep.last_weight = weight;
+        if weight > rep.peak {
+            rep.peak = weight;
+            rep.peak_time = time;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> RepDetector {
+        RepDetector::new(RepDetectionConfig {
+            enabled: true,
+            ..RepDetectionConfig::DISABLED
+        })
+    }
+
+    /// Feed `weights` sampled every 100 ms, returning the completed rep summaries
+    fn feed(detector: &mut RepDetector, weights: &[f32]) -> [Option<RepSummary>; 8] {
+        let mut summaries = [None; 8];
+        for (i, &weight) in weights.iter().enumerate() {
+            summaries[i] = detector.update(weight, Instant::from_millis(100 * i as u64));
+        }
+        summaries
+    }
+
+    #[test]
+    fn disabled_detects_nothing() {
+        let mut detector = RepDetector::new(RepDetectionConfig::DISABLED);
+        let summaries = feed(&mut detector, &[0.0, 10.0, 10.0, 10.0, 10.0, 0.0]);
+        assert_eq!(summaries, [None; 8]);
+    }
+
+    #[test]
+    fn summarizes_completed_rep() {
+        let mut detector = detector();
+        let summaries = feed(&mut detector, &[0.0, 4.0, 8.0, 6.0, 4.0, 0.0]);
+        assert_eq!(summaries[..5], [None; 5]);
+        let summary = summaries[5].expect("rep completed below the end threshold");
+        assert_eq!(summary.peak, 8.0);
+        assert!((summary.impulse - 1.8).abs() < 1e-5);
+        assert!((summary.average - 6.0).abs() < 1e-4);
+        assert_eq!(summary.time_under_tension_ms, 300);
+        assert_eq!(summary.time_to_peak_ms, 100);
+    }
+
+    #[test]
+    fn hysteresis_keeps_rep_going_between_thresholds() {
+        let mut detector = detector();
+        let summaries = feed(&mut detector, &[4.0, 2.0, 4.0, 2.0, 1.0]);
+        let summary = summaries[4].expect("rep completed below the end threshold");
+        assert_eq!(summary.time_under_tension_ms, 300);
+    }
+
+    #[test]
+    fn ignores_reps_shorter_than_minimum() {
+        let mut detector = detector();
+        let summaries = feed(&mut detector, &[4.0, 4.0, 0.0]);
+        assert_eq!(summaries, [None; 8]);
+    }
+
+    #[test]
+    fn reset_discards_rep_in_progress() {
+        let mut detector = detector();
+        feed(&mut detector, &[4.0, 4.0, 4.0, 4.0]);
+        detector.reset();
+        assert_eq!(detector.update(0.0, Instant::from_millis(400)), None);
+    }
+
+    #[test]
+    fn config_round_trips_through_bytes() {
+        let config = RepDetectionConfig {
+            enabled: true,
+            start_threshold: 5.0,
+            end_threshold: 2.5,
+            min_duration: Duration::from_millis(500),
+        };
+        assert_eq!(
+            RepDetectionConfig::from_bytes(&config.to_bytes()),
+            Some(config)
+        );
+    }
+
+    #[test]
+    fn config_from_bytes_rejects_invalid() {
+        let inverted = RepDetectionConfig {
+            start_threshold: 1.0,
+            end_threshold: 2.0,
+            ..RepDetectionConfig::DISABLED
+        };
+        assert_eq!(RepDetectionConfig::from_bytes(&inverted.to_bytes()), None);
+        assert_eq!(RepDetectionConfig::from_bytes(&[1, 0, 0]), None);
+    }
+}
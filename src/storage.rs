@@ -0,0 +1,703 @@
+/// Persistent storage
+///
+/// Shares the flash storage between the modules that persist data. Settings and logs
+/// are stored in the NVS partition and recorded sessions in the sessions partition of
+/// `partitions.csv`, at fixed addresses.
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use defmt::error;
+use embedded_storage::{ReadStorage, Storage, nor_flash::NorFlash};
+use esp_storage::FlashStorage;
+
+use crate::{
+    calibration::{CalibrationModel, CalibrationPoint, MAX_CALIBRATION_POINTS, ModelKind},
+    filter::FilterConfig,
+    hx711::{SensorHealth, TemperatureCompensation},
+    recording::{AutoRecordConfig, RECORD_SIZE, RecordReader},
+    reps::RepDetectionConfig,
+    transfer::{MAX_RECORD_SIZE, Records, Resource},
+};
+
+/// Size of a flash sector, the unit of erasure
+pub const SECTOR_SIZE: u32 = 4096;
+
+/// Address of the calibration factor (`f32`, little-endian)
+pub const CALIBRATION_FACTOR_ADDR: u32 = 0x9000;
+/// Address of the measurement filter configuration
+pub const FILTER_CONFIG_ADDR: u32 = 0x9004;
+/// Address of the calibration model
+pub const CALIBRATION_MODEL_ADDR: u32 = 0x9010;
+/// Address of the calibration temperature (`f32`, little-endian, NaN if unknown)
+pub const CALIBRATION_TEMPERATURE_ADDR: u32 = 0x90B8;
+/// Address of the temperature compensation
+pub const TEMPERATURE_COMPENSATION_ADDR: u32 = 0x90C0;
+/// Address of the automatic recording configuration
+pub const AUTO_RECORD_CONFIG_ADDR: u32 = 0x90C8;
+/// Address of the repetition detection configuration
+pub const REP_DETECTION_CONFIG_ADDR: u32 = 0x90D0;
+/// Address of the fault log, in its own two sectors
+const FAULT_LOG_ADDR: u32 = 0xA000;
+/// Maximum number of fault log entries, the oldest are dropped
+pub const MAX_FAULT_LOG_ENTRIES: usize = 32;
+/// Address of the calibration history, in its own two sectors
+const CALIBRATION_HISTORY_ADDR: u32 = 0xD000;
+/// Maximum number of calibration history entries, the oldest are dropped
+pub const MAX_CALIBRATION_HISTORY_ENTRIES: usize = 16;
+/// Address of the write-once factory calibration, in its own sector so that user
+/// calibration writes never touch it
+const FACTORY_CALIBRATION_ADDR: u32 = 0xC000;
+/// Address of the factory calibration temperature (`f32`, little-endian, NaN if unknown)
+const FACTORY_CALIBRATION_TEMPERATURE_ADDR: u32 =
+    FACTORY_CALIBRATION_ADDR + CalibrationModel::SIZE as u32;
+/// Address of the recorded sessions, the start of the sessions partition
+pub const SESSIONS_ADDR: u32 = 0x210000;
+/// Size of the sessions partition
+pub const SESSIONS_SIZE: u32 = 0x1F0000;
+
+/// Fault log
+pub const FAULT_LOG: RecordLog<{ FaultRecord::SIZE }> =
+    RecordLog::new(FAULT_LOG_ADDR, MAX_FAULT_LOG_ENTRIES);
+
+/// Calibration history
+pub const CALIBRATION_HISTORY: RecordLog<{ HistoryRecord::SIZE }> =
+    RecordLog::new(CALIBRATION_HISTORY_ADDR, MAX_CALIBRATION_HISTORY_ENTRIES);
+
+/// Flash storage, available once [`init`] has been called
+static FLASH: Mutex<RefCell<Option<FlashStorage<'static>>>> = Mutex::new(RefCell::new(None));
+
+/// Custom error type for storage operations
+#[derive(Debug)]
+pub enum StorageError {
+    /// Storage used before [`init`]
+    NotInitialized,
+    /// Flash read or write failed
+    FlashError,
+    /// Stored data is missing or invalid
+    InvalidData,
+    /// Write-once data was already written
+    Locked,
+}
+
+/// Hand the flash storage over to this module.
+pub fn init(flash: FlashStorage<'static>) {
+    critical_section::with(|cs| {
+        FLASH.borrow_ref_mut(cs).replace(flash);
+    });
+}
+
+/// Read `bytes.len()` bytes from `address`.
+pub fn read(address: u32, bytes: &mut [u8]) -> Result<(), StorageError> {
+    critical_section::with(|cs| {
+        let mut flash = FLASH.borrow_ref_mut(cs);
+        let flash = flash.as_mut().ok_or(StorageError::NotInitialized)?;
+        flash.read(address, bytes).map_err(|_| {
+            error!("Failed to read from flash at {:#x}", address);
+            StorageError::FlashError
+        })
+    })
+}
+
+/// Write `bytes` to `address`, preserving the rest of the sector.
+pub fn write(address: u32, bytes: &[u8]) -> Result<(), StorageError> {
+    critical_section::with(|cs| {
+        let mut flash = FLASH.borrow_ref_mut(cs);
+        let flash = flash.as_mut().ok_or(StorageError::NotInitialized)?;
+        // Read-modify-write, preserving the rest of the sector
+        Storage::write(flash, address, bytes).map_err(|_| {
+            error!("Failed to write to flash at {:#x}", address);
+            StorageError::FlashError
+        })
+    })
+}
+
+/// Program `bytes` at the erased, word-aligned `address`, without erasing first.
+pub fn program(address: u32, bytes: &[u8]) -> Result<(), StorageError> {
+    critical_section::with(|cs| {
+        let mut flash = FLASH.borrow_ref_mut(cs);
+        let flash = flash.as_mut().ok_or(StorageError::NotInitialized)?;
+        NorFlash::write(flash, address, bytes).map_err(|_| {
+            error!("Failed to program flash at {:#x}", address);
+            StorageError::FlashError
+        })
+    })
+}
+
+/// Erase the sectors covering `len` bytes from the sector-aligned `address`.
+pub fn erase(address: u32, len: u32) -> Result<(), StorageError> {
+    let end = (address + len).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+    critical_section::with(|cs| {
+        let mut flash = FLASH.borrow_ref_mut(cs);
+        let flash = flash.as_mut().ok_or(StorageError::NotInitialized)?;
+        NorFlash::erase(flash, address, end).map_err(|_| {
+            error!("Failed to erase flash at {:#x}", address);
+            StorageError::FlashError
+        })
+    })
+}
+
+/// Read the persisted measurement filter configuration.
+pub fn read_filter_config() -> Result<FilterConfig, StorageError> {
+    let mut bytes = [0u8; FilterConfig::SIZE];
+    read(FILTER_CONFIG_ADDR, &mut bytes)?;
+    FilterConfig::from_bytes(&bytes).ok_or(StorageError::InvalidData)
+}
+
+/// Persist the measurement filter configuration.
+pub fn write_filter_config(config: &FilterConfig) -> Result<(), StorageError> {
+    write(FILTER_CONFIG_ADDR, &config.to_bytes())
+}
+
+/// Read the persisted calibration model.
+pub fn read_calibration_model() -> Result<CalibrationModel, StorageError> {
+    let mut bytes = [0u8; CalibrationModel::SIZE];
+    read(CALIBRATION_MODEL_ADDR, &mut bytes)?;
+    CalibrationModel::from_bytes(&bytes).ok_or(StorageError::InvalidData)
+}
+
+/// Persist the calibration model along with its scale factor around zero.
+///
+/// Both go into a single write, so a power loss cannot leave them disagreeing. The
+/// factor keeps its own address, read by the firmware versions without models.
+pub fn write_calibration_model(model: &CalibrationModel) -> Result<(), StorageError> {
+    const MODEL_OFFSET: usize = (CALIBRATION_MODEL_ADDR - CALIBRATION_FACTOR_ADDR) as usize;
+
+    let mut bytes = [0u8; MODEL_OFFSET + CalibrationModel::SIZE];
+    // Keep the settings stored between the factor and the model
+    read(CALIBRATION_FACTOR_ADDR, &mut bytes)?;
+    bytes[..4].copy_from_slice(&model.factor().to_le_bytes());
+    bytes[MODEL_OFFSET..].copy_from_slice(&model.to_bytes());
+    write(CALIBRATION_FACTOR_ADDR, &bytes)
+}
+
+/// Read the persisted calibration temperature.
+pub fn read_calibration_temperature() -> Result<f32, StorageError> {
+    let mut bytes = [0u8; 4];
+    read(CALIBRATION_TEMPERATURE_ADDR, &mut bytes)?;
+    let temperature = f32::from_le_bytes(bytes);
+    if temperature.is_finite() {
+        Ok(temperature)
+    } else {
+        Err(StorageError::InvalidData)
+    }
+}
+
+/// Persist the calibration temperature, None if it is unknown.
+pub fn write_calibration_temperature(temperature: Option<f32>) -> Result<(), StorageError> {
+    write(
+        CALIBRATION_TEMPERATURE_ADDR,
+        &temperature.unwrap_or(f32::NAN).to_le_bytes(),
+    )
+}
+
+/// Read the persisted temperature compensation.
+pub fn read_temperature_compensation() -> Result<TemperatureCompensation, StorageError> {
+    let mut bytes = [0u8; TemperatureCompensation::SIZE];
+    read(TEMPERATURE_COMPENSATION_ADDR, &mut bytes)?;
+    TemperatureCompensation::from_bytes(&bytes).ok_or(StorageError::InvalidData)
+}
+
+/// Persist the temperature compensation.
+pub fn write_temperature_compensation(
+    compensation: &TemperatureCompensation,
+) -> Result<(), StorageError> {
+    write(TEMPERATURE_COMPENSATION_ADDR, &compensation.to_bytes())
+}
+
+/// Read the persisted automatic recording configuration.
+pub fn read_auto_record_config() -> Result<AutoRecordConfig, StorageError> {
+    let mut bytes = [0u8; AutoRecordConfig::SIZE];
+    read(AUTO_RECORD_CONFIG_ADDR, &mut bytes)?;
+    AutoRecordConfig::from_bytes(&bytes).ok_or(StorageError::InvalidData)
+}
+
+/// Persist the automatic recording configuration.
+pub fn write_auto_record_config(config: &AutoRecordConfig) -> Result<(), StorageError> {
+    write(AUTO_RECORD_CONFIG_ADDR, &config.to_bytes())
+}
+
+/// Read the persisted repetition detection configuration.
+pub fn read_rep_detection_config() -> Result<RepDetectionConfig, StorageError> {
+    let mut bytes = [0u8; RepDetectionConfig::SIZE];
+    read(REP_DETECTION_CONFIG_ADDR, &mut bytes)?;
+    RepDetectionConfig::from_bytes(&bytes).ok_or(StorageError::InvalidData)
+}
+
+/// Persist the repetition detection configuration.
+pub fn write_rep_detection_config(config: &RepDetectionConfig) -> Result<(), StorageError> {
+    write(REP_DETECTION_CONFIG_ADDR, &config.to_bytes())
+}
+
+/// Read the factory calibration model and its temperature, if known.
+pub fn read_factory_calibration() -> Result<(CalibrationModel, Option<f32>), StorageError> {
+    let mut model = [0u8; CalibrationModel::SIZE];
+    read(FACTORY_CALIBRATION_ADDR, &mut model)?;
+    let model = CalibrationModel::from_bytes(&model).ok_or(StorageError::InvalidData)?;
+
+    let mut temperature = [0u8; 4];
+    read(FACTORY_CALIBRATION_TEMPERATURE_ADDR, &mut temperature)?;
+    let temperature = f32::from_le_bytes(temperature);
+    Ok((model, temperature.is_finite().then_some(temperature)))
+}
+
+/// Persist the factory calibration model and its temperature.
+///
+/// The factory calibration can only be written once, while its slot is erased.
+pub fn write_factory_calibration(
+    model: &CalibrationModel,
+    temperature: Option<f32>,
+) -> Result<(), StorageError> {
+    let mut bytes = [0u8; CalibrationModel::SIZE + 4];
+    read(FACTORY_CALIBRATION_ADDR, &mut bytes)?;
+    if bytes.iter().any(|&byte| byte != 0xFF) {
+        return Err(StorageError::Locked);
+    }
+
+    write(FACTORY_CALIBRATION_ADDR, &model.to_bytes())?;
+    write(
+        FACTORY_CALIBRATION_TEMPERATURE_ADDR,
+        &temperature.unwrap_or(f32::NAN).to_le_bytes(),
+    )
+}
+
+/// Bounded log of fixed-size records, stored in two dedicated flash sectors
+///
+/// Each record is prefixed with a sequence number (`u32`, little-endian) in a word-aligned
+/// slot, so records are programmed into erased slots without erasing anything. Records
+/// fill one sector, then the other, which is erased first; the newest `capacity` records
+/// are kept and those of the full sector survive the erase. An erased sequence number
+/// ends the records of a sector.
+#[derive(Clone, Copy, Debug)]
+pub struct RecordLog<const SIZE: usize> {
+    /// Sector-aligned start address
+    address: u32,
+    /// Maximum number of records, and of slots in each sector
+    capacity: usize,
+}
+
+/// Where the records of a [`RecordLog`] are, valid until the next append
+#[derive(Clone, Copy, Debug)]
+pub struct LogScan {
+    /// Sector holding the newest records
+    newest: usize,
+    /// Number of records of each sector, from its first slot
+    counts: [usize; 2],
+    /// Number of records, up to the capacity
+    len: usize,
+    /// Sequence number of the newest record
+    sequence: Option<u32>,
+}
+
+impl LogScan {
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if there are no records
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<const SIZE: usize> RecordLog<SIZE> {
+    /// Sequence number of an empty slot
+    const EMPTY: u32 = u32::MAX;
+    /// Size of a slot: sequence number and record, padded to a word
+    const SLOT_SIZE: usize = (4 + SIZE).next_multiple_of(4);
+
+    /// Create a log of `capacity` records in the two sectors from the sector-aligned
+    /// `address`
+    pub const fn new(address: u32, capacity: usize) -> Self {
+        assert!(capacity * Self::SLOT_SIZE <= SECTOR_SIZE as usize);
+        Self { address, capacity }
+    }
+
+    fn sector_address(&self, sector: usize) -> u32 {
+        self.address + sector as u32 * SECTOR_SIZE
+    }
+
+    fn slot_address(&self, sector: usize, slot: usize) -> u32 {
+        self.sector_address(sector) + (slot * Self::SLOT_SIZE) as u32
+    }
+
+    fn sequence(&self, sector: usize, slot: usize) -> Result<u32, StorageError> {
+        let mut bytes = [0u8; 4];
+        read(self.slot_address(sector, slot), &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Find the records of each sector and which sector is the newest
+    pub fn scan(&self) -> Result<LogScan, StorageError> {
+        let mut counts = [0; 2];
+        let mut first = [None; 2];
+        let mut last = [None; 2];
+        for sector in 0..2 {
+            for slot in 0..self.capacity {
+                let sequence = self.sequence(sector, slot)?;
+                if sequence == Self::EMPTY {
+                    break;
+                }
+                first[sector].get_or_insert(sequence);
+                last[sector] = Some(sequence);
+                counts[sector] += 1;
+            }
+        }
+        let newest = match first {
+            [Some(a), Some(b)] if b > a => 1,
+            [None, Some(_)] => 1,
+            _ => 0,
+        };
+        Ok(LogScan {
+            newest,
+            counts,
+            len: (counts[0] + counts[1]).min(self.capacity),
+            sequence: last[newest],
+        })
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> Result<usize, StorageError> {
+        Ok(self.scan()?.len())
+    }
+
+    /// Check if the log has no records
+    pub fn is_empty(&self) -> Result<bool, StorageError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Check if a slot is erased, so a record can be programmed into it
+    fn is_erased(&self, sector: usize, slot: usize) -> Result<bool, StorageError> {
+        let mut bytes = [0u8; SIZE];
+        read(self.slot_address(sector, slot) + 4, &mut bytes)?;
+        Ok(bytes.iter().all(|&byte| byte == 0xFF))
+    }
+
+    /// Append a record, dropping the oldest one if the log is full
+    pub fn append(&self, record: &[u8; SIZE]) -> Result<(), StorageError> {
+        let scan = self.scan()?;
+        let sequence = scan.sequence.map_or(0, |sequence| sequence + 1);
+        let mut sector = scan.newest;
+        let mut slot = scan.counts[sector];
+        // Move on to the other sector once this one is full, or when the next slot
+        // holds the record of an interrupted append
+        if slot == self.capacity || !self.is_erased(sector, slot)? {
+            sector = 1 - sector;
+            slot = 0;
+            erase(self.sector_address(sector), SECTOR_SIZE)?;
+        }
+
+        let address = self.slot_address(sector, slot);
+        let aligned = SIZE / 4 * 4;
+        if aligned > 0 {
+            program(address + 4, &record[..aligned])?;
+        }
+        if aligned < SIZE {
+            let mut tail = [0xFF; 4];
+            tail[..SIZE - aligned].copy_from_slice(&record[aligned..]);
+            program(address + 4 + aligned as u32, &tail)?;
+        }
+        // The sequence number goes last, an interrupted append leaves its slot without
+        // one, and the record is then skipped
+        program(address, &sequence.to_le_bytes())
+    }
+
+    /// Read the record at `index`, 0 being the oldest
+    pub fn read(&self, index: usize) -> Result<Option<[u8; SIZE]>, StorageError> {
+        self.read_scanned(&self.scan()?, index)
+    }
+
+    /// Read the record at `index` of an earlier scan, 0 being the oldest
+    ///
+    /// Saves scanning the log again when reading many records.
+    pub fn read_scanned(
+        &self,
+        scan: &LogScan,
+        index: usize,
+    ) -> Result<Option<[u8; SIZE]>, StorageError> {
+        if index >= scan.len {
+            return Ok(None);
+        }
+
+        // Skip the records beyond the capacity, the oldest sector holds the first ones
+        let oldest = 1 - scan.newest;
+        let position = scan.counts[0] + scan.counts[1] - scan.len + index;
+        let (sector, slot) = if position < scan.counts[oldest] {
+            (oldest, position)
+        } else {
+            (scan.newest, position - scan.counts[oldest])
+        };
+
+        let mut record = [0u8; SIZE];
+        read(self.slot_address(sector, slot) + 4, &mut record)?;
+        Ok(Some(record))
+    }
+
+    /// Remove all records
+    pub fn clear(&self) -> Result<(), StorageError> {
+        erase(self.address, 2 * SECTOR_SIZE)
+    }
+}
+
+/// Stored records of a bulk transfer resource
+#[derive(Clone, Copy, Debug)]
+pub enum StoredRecords {
+    /// Recorded sessions
+    Sessions(RecordReader),
+    /// Fault log
+    FaultLog(LogScan),
+    /// Calibration history
+    CalibrationHistory(LogScan),
+}
+
+impl StoredRecords {
+    /// Locate the records of `resource` stored at the time of the call
+    ///
+    /// The logs are scanned once, a transfer reads them many times.
+    pub fn open(resource: Resource) -> Result<Self, StorageError> {
+        Ok(match resource {
+            Resource::Sessions => Self::Sessions(RecordReader::open()?),
+            Resource::FaultLog => Self::FaultLog(FAULT_LOG.scan()?),
+            Resource::CalibrationHistory => Self::CalibrationHistory(CALIBRATION_HISTORY.scan()?),
+        })
+    }
+}
+
+impl Records for StoredRecords {
+    type Error = StorageError;
+
+    fn record_size(&self) -> usize {
+        const { assert!(HistoryRecord::SIZE <= MAX_RECORD_SIZE) };
+        match self {
+            Self::Sessions(_) => RECORD_SIZE,
+            Self::FaultLog(_) => FaultRecord::SIZE,
+            Self::CalibrationHistory(_) => HistoryRecord::SIZE,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Sessions(reader) => reader.record_count() as usize,
+            Self::FaultLog(scan) | Self::CalibrationHistory(scan) => scan.len(),
+        }
+    }
+
+    fn read_record(&self, index: usize, record: &mut [u8]) -> Result<(), StorageError> {
+        match self {
+            Self::Sessions(reader) => {
+                let bytes = reader
+                    .read_raw(index as u32)?
+                    .ok_or(StorageError::InvalidData)?;
+                record.copy_from_slice(&bytes);
+            }
+            Self::FaultLog(scan) => {
+                let bytes = FAULT_LOG
+                    .read_scanned(scan, index)?
+                    .ok_or(StorageError::InvalidData)?;
+                record.copy_from_slice(&bytes);
+            }
+            Self::CalibrationHistory(scan) => {
+                let bytes = CALIBRATION_HISTORY
+                    .read_scanned(scan, index)?
+                    .ok_or(StorageError::InvalidData)?;
+                record.copy_from_slice(&bytes);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fault log entry
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaultRecord {
+    /// Sensor health when the fault was detected
+    pub health: SensorHealth,
+    /// Uptime when the fault was detected, in milliseconds
+    pub uptime_ms: u32,
+}
+
+impl FaultRecord {
+    /// Size of the serialized record in bytes
+    pub const SIZE: usize = 5;
+
+    /// Serialize as health code and little-endian uptime
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = self.health as u8;
+        bytes[1..5].copy_from_slice(&self.uptime_ms.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a record, returning None if the health code is unknown
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let health = match bytes[0] {
+            0 => SensorHealth::Ok,
+            1 => SensorHealth::NotResponding,
+            2 => SensorHealth::Saturated,
+            3 => SensorHealth::StuckValue,
+            _ => return None,
+        };
+        Some(Self {
+            health,
+            uptime_ms: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]),
+        })
+    }
+}
+
+/// Size of the firmware version stored in history records
+pub const FIRMWARE_VERSION_SIZE: usize = 7;
+
+/// Calibration history event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HistoryEvent {
+    /// Calibration applied, with the scale factor around zero and the fitted points
+    Calibration {
+        model: ModelKind,
+        factor: f32,
+        points: [CalibrationPoint; MAX_CALIBRATION_POINTS],
+        point_count: usize,
+    },
+    /// Check of the calibration against a known weight, in kg
+    Verification {
+        reference: f32,
+        measured: f32,
+        passed: bool,
+    },
+}
+
+impl HistoryEvent {
+    /// Size of the largest serialized event in bytes
+    const SIZE: usize = 7 + 8 * MAX_CALIBRATION_POINTS;
+
+    /// Event type, as serialized and reported
+    pub fn kind(&self) -> u8 {
+        match self {
+            HistoryEvent::Calibration { .. } => 0,
+            HistoryEvent::Verification { .. } => 1,
+        }
+    }
+}
+
+/// Calibration history entry
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistoryRecord {
+    /// Recorded event
+    pub event: HistoryEvent,
+    /// Uptime when the event was recorded, in milliseconds
+    pub uptime_ms: u32,
+    /// Temperature when the event was recorded, in °C
+    pub temperature: Option<f32>,
+    /// Firmware version, zero padded
+    pub firmware_version: [u8; FIRMWARE_VERSION_SIZE],
+}
+
+impl HistoryRecord {
+    /// Offset of the firmware version in the serialized record
+    const VERSION_OFFSET: usize = 8;
+    /// Offset of the event in the serialized record
+    const EVENT_OFFSET: usize = Self::VERSION_OFFSET + FIRMWARE_VERSION_SIZE;
+    /// Size of the serialized record in bytes
+    pub const SIZE: usize = Self::EVENT_OFFSET + HistoryEvent::SIZE;
+
+    /// Create a record of `event` for the running firmware
+    pub fn new(event: HistoryEvent, uptime_ms: u32, temperature: Option<f32>) -> Self {
+        let version = env!("DEVICE_VERSION_NUMBER").as_bytes();
+        let len = version.len().min(FIRMWARE_VERSION_SIZE);
+        let mut firmware_version = [0u8; FIRMWARE_VERSION_SIZE];
+        firmware_version[..len].copy_from_slice(&version[..len]);
+        Self {
+            event,
+            uptime_ms,
+            temperature,
+            firmware_version,
+        }
+    }
+
+    /// Serialize as little-endian uptime and temperature (NaN if unknown), firmware
+    /// version, then the event type and its data
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.uptime_ms.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.temperature.unwrap_or(f32::NAN).to_le_bytes());
+        bytes[Self::VERSION_OFFSET..Self::EVENT_OFFSET].copy_from_slice(&self.firmware_version);
+        let event = &mut bytes[Self::EVENT_OFFSET..];
+        event[0] = self.event.kind();
+        match self.event {
+            HistoryEvent::Calibration {
+                model,
+                factor,
+                points,
+                point_count,
+            } => {
+                event[1] = model as u8;
+                event[2..6].copy_from_slice(&factor.to_le_bytes());
+                event[6] = point_count as u8;
+                for (i, (raw_value, weight)) in points[..point_count].iter().enumerate() {
+                    let offset = 7 + 8 * i;
+                    event[offset..offset + 4].copy_from_slice(&raw_value.to_le_bytes());
+                    event[offset + 4..offset + 8].copy_from_slice(&weight.to_le_bytes());
+                }
+            }
+            HistoryEvent::Verification {
+                reference,
+                measured,
+                passed,
+            } => {
+                event[1..5].copy_from_slice(&reference.to_le_bytes());
+                event[5..9].copy_from_slice(&measured.to_le_bytes());
+                event[9] = passed as u8;
+            }
+        }
+        bytes
+    }
+
+    /// Deserialize a record, returning None if the event type is unknown
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let f32_at = |offset: usize| {
+            f32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let event = Self::EVENT_OFFSET;
+        let event = match bytes[event] {
+            0 => {
+                let model = match bytes[event + 1] {
+                    0 => ModelKind::Linear,
+                    1 => ModelKind::Quadratic,
+                    2 => ModelKind::Piecewise,
+                    _ => return None,
+                };
+                let point_count = (bytes[event + 6] as usize).min(MAX_CALIBRATION_POINTS);
+                let mut points = [(0.0, 0.0); MAX_CALIBRATION_POINTS];
+                for (i, point) in points[..point_count].iter_mut().enumerate() {
+                    let offset = event + 7 + 8 * i;
+                    *point = (f32_at(offset), f32_at(offset + 4));
+                }
+                HistoryEvent::Calibration {
+                    model,
+                    factor: f32_at(event + 2),
+                    points,
+                    point_count,
+                }
+            }
+            1 => HistoryEvent::Verification {
+                reference: f32_at(event + 1),
+                measured: f32_at(event + 5),
+                passed: bytes[event + 9] != 0,
+            },
+            _ => return None,
+        };
+        let temperature = f32_at(4);
+        Some(Self {
+            event,
+            uptime_ms: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            temperature: temperature.is_finite().then_some(temperature),
+            firmware_version: bytes[Self::VERSION_OFFSET..Self::EVENT_OFFSET]
+                .try_into()
+                .ok()?,
+        })
+    }
+}
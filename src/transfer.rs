@@ -0,0 +1,572 @@
+/// Bulk transfer of stored data
+///
+/// Stored data (recorded sessions, fault log, calibration history) is exposed as a
+/// stream of serialized records and sent in chunks on a dedicated characteristic. Each
+/// chunk carries its offset and a CRC-32 of its data. The client acknowledges the
+/// received offset and at most a window of chunks is sent ahead of the acknowledged
+/// one; unacknowledged chunks are sent again after a timeout. A transfer can be
+/// resumed by starting it again at the last acknowledged offset.
+///
+/// Offsets count from the oldest record, so they move when it is overwritten or the
+/// resource is cleared. Chunks carry the generation of the resource, a CRC-32 of its
+/// oldest record, and a resume must give the generation of the interrupted transfer. If
+/// it changed, the resume is refused and the transfer has to start over.
+use core::fmt::Debug;
+
+use defmt::{Format, error, warn};
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, channel::Channel};
+#[cfg(target_arch = "riscv32")]
+use trouble_host::types::gatt_traits::{AsGatt, FromGatt, FromGattError};
+
+/// Size of the chunk header: resource, flags, offset (`u32`), generation (`u32`) and
+/// CRC-32 (`u32`)
+const CHUNK_HEADER_SIZE: usize = 14;
+/// Maximum data size of a chunk, the chunk fits in the largest ATT MTU
+pub const MAX_CHUNK_DATA_SIZE: usize = 230;
+/// Data size of a chunk fitting in the default ATT MTU of 23 bytes
+const DEFAULT_CHUNK_DATA_SIZE: u8 = 6;
+/// Default number of chunks sent ahead of the acknowledged offset
+const DEFAULT_WINDOW: u8 = 8;
+/// Maximum size of a serialized record of any resource
+pub const MAX_RECORD_SIZE: usize = 256;
+/// Size of the transfer control characteristic
+pub const TRANSFER_CONTROL_SIZE: usize = 12;
+
+/// Size of the channel used to forward transfer commands
+const TRANSFER_COMMAND_CHANNEL_SIZE: usize = 4;
+/// Channel used to forward transfer commands from the GATT events
+pub type TransferCommandChannel =
+    Channel<NoopRawMutex, TransferCommand, TRANSFER_COMMAND_CHANNEL_SIZE>;
+
+/// Last chunk of the resource
+const FLAG_LAST: u8 = 1;
+/// Reading the resource failed, the transfer is aborted
+const FLAG_ERROR: u8 = 2;
+/// The resource changed since the interrupted transfer, the resume is refused
+const FLAG_CHANGED: u8 = 4;
+
+/// Stored data available for transfer
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+#[repr(u8)]
+pub enum Resource {
+    /// Recorded session records
+    Sessions = 0,
+    /// Fault log records
+    FaultLog = 1,
+    /// Calibration history records
+    CalibrationHistory = 2,
+}
+
+impl Resource {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Resource::Sessions),
+            1 => Some(Resource::FaultLog),
+            2 => Some(Resource::CalibrationHistory),
+            _ => None,
+        }
+    }
+}
+
+/// Command written to the transfer control characteristic
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub enum TransferCommand {
+    /// Start sending a resource from a byte offset, with the chunk data size and window,
+    /// and the generation of the interrupted transfer when resuming
+    Start {
+        resource: Resource,
+        offset: u32,
+        chunk_size: u8,
+        window: u8,
+        generation: Option<u32>,
+    },
+    /// Every byte before the offset was received
+    Ack(u32),
+    /// Stop the transfer
+    Abort,
+}
+
+impl TransferCommand {
+    /// Parse a command: start (0x01) with resource (u8), offset (u32) and optionally
+    /// chunk data size (u8), window (u8) and generation (u32), acknowledge (0x02) with
+    /// offset (u32), or abort (0x03). Returns None if the command is invalid.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let offset = |start: usize| {
+            data.get(start..start + 4)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(u32::from_le_bytes)
+        };
+        match *data.first()? {
+            0x01 => Some(TransferCommand::Start {
+                resource: Resource::from_u8(*data.get(1)?)?,
+                offset: offset(2)?,
+                chunk_size: data
+                    .get(6)
+                    .map_or(DEFAULT_CHUNK_DATA_SIZE, |&size| {
+                        size.min(MAX_CHUNK_DATA_SIZE as u8)
+                    })
+                    .max(1),
+                window: data.get(7).map_or(DEFAULT_WINDOW, |&window| window.max(1)),
+                generation: offset(8),
+            }),
+            0x02 => Some(TransferCommand::Ack(offset(1)?)),
+            0x03 => Some(TransferCommand::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// Chunk of a resource
+#[derive(Clone, Copy, Debug)]
+pub struct Chunk {
+    /// Serialized header and data
+    bytes: [u8; CHUNK_HEADER_SIZE + MAX_CHUNK_DATA_SIZE],
+    /// Serialized size
+    len: usize,
+}
+
+impl Chunk {
+    fn new(resource: Resource, flags: u8, offset: u32, generation: u32, data: &[u8]) -> Self {
+        let mut bytes = [0u8; CHUNK_HEADER_SIZE + MAX_CHUNK_DATA_SIZE];
+        bytes[0] = resource as u8;
+        bytes[1] = flags;
+        bytes[2..6].copy_from_slice(&offset.to_le_bytes());
+        bytes[6..10].copy_from_slice(&generation.to_le_bytes());
+        bytes[10..14].copy_from_slice(&crc32(data).to_le_bytes());
+        bytes[CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + data.len()].copy_from_slice(data);
+        Self {
+            bytes,
+            len: CHUNK_HEADER_SIZE + data.len(),
+        }
+    }
+
+    /// Serialized header and data
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new(Resource::Sessions, FLAG_LAST, 0, 0, &[])
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+impl AsGatt for Chunk {
+    const MIN_SIZE: usize = CHUNK_HEADER_SIZE;
+    const MAX_SIZE: usize = CHUNK_HEADER_SIZE + MAX_CHUNK_DATA_SIZE;
+
+    fn as_gatt(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+impl FromGatt for Chunk {
+    fn from_gatt(data: &[u8]) -> Result<Self, FromGattError> {
+        if data.len() < Self::MIN_SIZE || data.len() > Self::MAX_SIZE {
+            return Err(FromGattError::InvalidLength);
+        }
+        let mut bytes = [0u8; CHUNK_HEADER_SIZE + MAX_CHUNK_DATA_SIZE];
+        bytes[..data.len()].copy_from_slice(data);
+        Ok(Self {
+            bytes,
+            len: data.len(),
+        })
+    }
+}
+
+/// CRC-32 (IEEE 802.3) of `data`
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Stored records of a resource, read by a transfer
+pub trait Records {
+    /// Error reading a record
+    type Error: Debug;
+
+    /// Size of a serialized record, at most [`MAX_RECORD_SIZE`]
+    fn record_size(&self) -> usize;
+
+    /// Number of records
+    fn len(&self) -> usize;
+
+    /// Check if there are no records
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read the serialized record at `index`, 0 being the oldest, into `record`
+    fn read_record(&self, index: usize, record: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+/// Transfer of a resource in progress
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer<R> {
+    /// Resource being transferred
+    resource: Resource,
+    /// Records of the resource, located when the transfer started
+    records: R,
+    /// Size of the resource in bytes, when the transfer started
+    size: u32,
+    /// Generation of the resource, the CRC-32 of its oldest record or 0 when empty
+    generation: u32,
+    /// The resource changed since the interrupted transfer being resumed
+    changed: bool,
+    /// Data size of a chunk
+    chunk_size: u32,
+    /// Maximum number of chunks sent ahead of the acknowledged offset
+    window: u32,
+    /// Offset of the next chunk to send
+    next: u32,
+    /// Every byte before this offset was received
+    acked: u32,
+    /// The last chunk was sent
+    sent_last: bool,
+    /// The transfer ended with an error chunk
+    failed: bool,
+}
+
+impl<R: Records> Transfer<R> {
+    /// Start transferring the `records` of `resource` from `offset`
+    ///
+    /// Resuming from a non-zero offset is refused unless `generation` matches the one of
+    /// the resource, the first chunk then reports the change.
+    pub fn start(
+        resource: Resource,
+        records: R,
+        offset: u32,
+        chunk_size: u8,
+        window: u8,
+        generation: Option<u32>,
+    ) -> Result<Self, R::Error> {
+        let size = (records.len() * records.record_size()) as u32;
+        let offset = offset.min(size);
+        let mut transfer = Self {
+            resource,
+            records,
+            size,
+            generation: 0,
+            changed: false,
+            chunk_size: chunk_size as u32,
+            window: window as u32,
+            next: offset,
+            acked: offset,
+            sent_last: false,
+            failed: false,
+        };
+        if size > 0 {
+            let mut record = [0u8; MAX_RECORD_SIZE];
+            let record = &mut record[..transfer.records.record_size()];
+            transfer.records.read_record(0, record)?;
+            transfer.generation = crc32(record);
+        }
+        transfer.changed = offset > 0 && generation != Some(transfer.generation);
+        Ok(transfer)
+    }
+
+    /// Check if a chunk can be sent within the window
+    pub fn can_send(&self) -> bool {
+        !self.sent_last && self.next - self.acked < self.window * self.chunk_size
+    }
+
+    /// Check if every byte was received or the transfer failed
+    pub fn is_finished(&self) -> bool {
+        self.failed || (self.sent_last && self.acked >= self.size)
+    }
+
+    /// Record that every byte before `offset` was received
+    pub fn ack(&mut self, offset: u32) {
+        self.acked = offset.clamp(self.acked, self.next);
+    }
+
+    /// Send the unacknowledged chunks again
+    pub fn rewind(&mut self) {
+        self.next = self.acked;
+        self.sent_last = false;
+    }
+
+    /// Read the next chunk
+    ///
+    /// A read failure ends the transfer with an error chunk.
+    pub fn next_chunk(&mut self) -> Chunk {
+        if self.changed {
+            warn!(
+                "{:?} changed since the transfer being resumed",
+                self.resource
+            );
+            self.sent_last = true;
+            self.failed = true;
+            return Chunk::new(self.resource, FLAG_CHANGED, 0, self.generation, &[]);
+        }
+
+        let len = self.chunk_size.min(self.size - self.next) as usize;
+        let mut data = [0u8; MAX_CHUNK_DATA_SIZE];
+        if let Err(e) = self.read(self.next, &mut data[..len]) {
+            error!(
+                "Failed to read {:?} for transfer: {:?}",
+                self.resource,
+                defmt::Debug2Format(&e)
+            );
+            self.sent_last = true;
+            self.failed = true;
+            return Chunk::new(self.resource, FLAG_ERROR, self.next, self.generation, &[]);
+        }
+
+        let offset = self.next;
+        self.next += len as u32;
+        self.sent_last = self.next >= self.size;
+        let flags = if self.sent_last { FLAG_LAST } else { 0 };
+        Chunk::new(self.resource, flags, offset, self.generation, &data[..len])
+    }
+
+    /// Fill `data` with the resource bytes starting at `offset`
+    fn read(&self, mut offset: u32, data: &mut [u8]) -> Result<(), R::Error> {
+        let record_size = self.records.record_size();
+        let mut record = [0u8; MAX_RECORD_SIZE];
+        let mut filled = 0;
+        while filled < data.len() {
+            let index = offset as usize / record_size;
+            let start = offset as usize % record_size;
+            self.records
+                .read_record(index, &mut record[..record_size])?;
+
+            let len = (record_size - start).min(data.len() - filled);
+            data[filled..filled + len].copy_from_slice(&record[start..start + len]);
+            filled += len;
+            offset += len as u32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records held in memory
+    #[derive(Clone, Copy, Debug)]
+    struct TestRecords {
+        bytes: &'static [u8],
+        record_size: usize,
+        /// Index of the record failing to read
+        failing: Option<usize>,
+    }
+
+    impl Records for TestRecords {
+        type Error = ();
+
+        fn record_size(&self) -> usize {
+            self.record_size
+        }
+
+        fn len(&self) -> usize {
+            self.bytes.len() / self.record_size
+        }
+
+        fn read_record(&self, index: usize, record: &mut [u8]) -> Result<(), ()> {
+            if self.failing == Some(index) {
+                return Err(());
+            }
+            let start = index * self.record_size;
+            record.copy_from_slice(&self.bytes[start..start + self.record_size]);
+            Ok(())
+        }
+    }
+
+    const BYTES: &[u8] = &[
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    ];
+
+    fn records() -> TestRecords {
+        TestRecords {
+            bytes: BYTES,
+            record_size: 4,
+            failing: None,
+        }
+    }
+
+    fn start(records: TestRecords, offset: u32, generation: Option<u32>) -> Transfer<TestRecords> {
+        Transfer::start(Resource::FaultLog, records, offset, 6, 2, generation).unwrap()
+    }
+
+    /// Flags, offset, generation and data of a chunk, checking its resource and CRC
+    fn parse(chunk: &Chunk) -> (u8, u32, u32, &[u8]) {
+        let bytes = chunk.as_bytes();
+        let word = |start: usize| u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap());
+        let data = &bytes[CHUNK_HEADER_SIZE..];
+        assert_eq!(bytes[0], Resource::FaultLog as u8);
+        assert_eq!(word(10), crc32(data));
+        (bytes[1], word(2), word(6), data)
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn command_from_bytes() {
+        assert_eq!(
+            TransferCommand::from_bytes(&[0x01, 2, 8, 0, 0, 0, 100, 4, 1, 0, 0, 0]),
+            Some(TransferCommand::Start {
+                resource: Resource::CalibrationHistory,
+                offset: 8,
+                chunk_size: 100,
+                window: 4,
+                generation: Some(1),
+            })
+        );
+        assert_eq!(
+            TransferCommand::from_bytes(&[0x01, 0, 0, 0, 0, 0]),
+            Some(TransferCommand::Start {
+                resource: Resource::Sessions,
+                offset: 0,
+                chunk_size: DEFAULT_CHUNK_DATA_SIZE,
+                window: DEFAULT_WINDOW,
+                generation: None,
+            })
+        );
+        assert_eq!(
+            TransferCommand::from_bytes(&[0x02, 0x10, 0x20, 0, 0]),
+            Some(TransferCommand::Ack(0x2010))
+        );
+        assert_eq!(
+            TransferCommand::from_bytes(&[0x03]),
+            Some(TransferCommand::Abort)
+        );
+    }
+
+    #[test]
+    fn command_from_bytes_bounds_chunk_size_and_window() {
+        let Some(TransferCommand::Start {
+            chunk_size, window, ..
+        }) = TransferCommand::from_bytes(&[0x01, 1, 0, 0, 0, 0, 255, 0])
+        else {
+            panic!("start command expected");
+        };
+        assert_eq!(chunk_size, MAX_CHUNK_DATA_SIZE as u8);
+        assert_eq!(window, 1);
+
+        let Some(TransferCommand::Start { chunk_size, .. }) =
+            TransferCommand::from_bytes(&[0x01, 1, 0, 0, 0, 0, 0])
+        else {
+            panic!("start command expected");
+        };
+        assert_eq!(chunk_size, 1);
+    }
+
+    #[test]
+    fn command_from_bytes_rejects_invalid() {
+        assert_eq!(TransferCommand::from_bytes(&[]), None);
+        assert_eq!(TransferCommand::from_bytes(&[0x04]), None);
+        assert_eq!(TransferCommand::from_bytes(&[0x01, 3, 0, 0, 0, 0]), None);
+        assert_eq!(TransferCommand::from_bytes(&[0x01, 0, 0, 0]), None);
+        assert_eq!(TransferCommand::from_bytes(&[0x02, 0]), None);
+    }
+
+    #[test]
+    fn sends_chunks_within_window_until_acknowledged() {
+        let mut transfer = start(records(), 0, None);
+        let generation = crc32(&BYTES[..4]);
+
+        assert_eq!(
+            parse(&transfer.next_chunk()),
+            (0, 0, generation, &BYTES[..6])
+        );
+        assert_eq!(
+            parse(&transfer.next_chunk()),
+            (0, 6, generation, &BYTES[6..12])
+        );
+        assert!(!transfer.can_send());
+
+        transfer.ack(6);
+        assert!(transfer.can_send());
+        assert_eq!(
+            parse(&transfer.next_chunk()),
+            (0, 12, generation, &BYTES[12..18])
+        );
+        transfer.ack(18);
+        assert_eq!(
+            parse(&transfer.next_chunk()),
+            (FLAG_LAST, 18, generation, &BYTES[18..])
+        );
+        assert!(!transfer.can_send());
+        assert!(!transfer.is_finished());
+
+        transfer.ack(20);
+        assert!(transfer.is_finished());
+    }
+
+    #[test]
+    fn rewind_sends_unacknowledged_chunks_again() {
+        let mut transfer = start(records(), 0, None);
+        transfer.next_chunk();
+        transfer.next_chunk();
+        transfer.ack(6);
+        transfer.rewind();
+        assert_eq!(parse(&transfer.next_chunk()).1, 6);
+    }
+
+    #[test]
+    fn ack_beyond_sent_chunks_is_ignored() {
+        let mut transfer = start(records(), 0, None);
+        transfer.next_chunk();
+        transfer.ack(20);
+        transfer.rewind();
+        assert_eq!(parse(&transfer.next_chunk()).1, 6);
+    }
+
+    #[test]
+    fn resume_with_matching_generation() {
+        let mut transfer = start(records(), 10, Some(crc32(&BYTES[..4])));
+        assert_eq!(parse(&transfer.next_chunk()).1, 10);
+    }
+
+    #[test]
+    fn resume_of_changed_resource_is_refused() {
+        for generation in [None, Some(0)] {
+            let mut transfer = start(records(), 10, generation);
+            let chunk = transfer.next_chunk();
+            let (flags, offset, _, data) = parse(&chunk);
+            assert_eq!((flags, offset, data), (FLAG_CHANGED, 0, &[][..]));
+            assert!(transfer.is_finished());
+        }
+    }
+
+    #[test]
+    fn read_failure_ends_transfer() {
+        let records = TestRecords {
+            failing: Some(2),
+            ..records()
+        };
+        let mut transfer = start(records, 0, None);
+        assert_eq!(parse(&transfer.next_chunk()).0, 0);
+        let chunk = transfer.next_chunk();
+        let (flags, offset, _, data) = parse(&chunk);
+        assert_eq!((flags, offset, data), (FLAG_ERROR, 6, &[][..]));
+        assert!(transfer.is_finished());
+    }
+
+    #[test]
+    fn empty_resource_sends_last_chunk() {
+        let records = TestRecords {
+            bytes: &[],
+            ..records()
+        };
+        let mut transfer = start(records, 0, None);
+        assert_eq!(parse(&transfer.next_chunk()), (FLAG_LAST, 0, 0, &[][..]));
+        transfer.ack(0);
+        assert!(transfer.is_finished());
+    }
+}
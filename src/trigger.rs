@@ -0,0 +1,117 @@
+/// Triggered measurement start
+///
+/// While armed, samples are kept in a ring buffer covering the pre-trigger time. The
+/// measurement starts with the first sample reaching the trigger threshold, the force
+/// onset, and the buffered samples preceding it are sent along so the start of the
+/// pull is not lost.
+use embassy_time::Duration;
+
+/// Longest pre-trigger time
+pub const MAX_PRE_TRIGGER_TIME: Duration = Duration::from_secs(1);
+/// Pre-trigger time when none is given
+pub const DEFAULT_PRE_TRIGGER_TIME: Duration = Duration::from_millis(250);
+/// Number of buffered samples, covering the longest pre-trigger time at 80 Hz
+const BUFFER_SIZE: usize = 96;
+
+/// Armed measurement waiting for the force onset
+#[derive(Clone, Copy, Debug)]
+pub struct ArmedTrigger {
+    /// Weight starting the measurement, in kg
+    threshold: f32,
+    /// Time covered by the buffered samples
+    pre_trigger: Duration,
+    /// Buffered samples (weight, timestamp in microseconds)
+    samples: [(f32, u64); BUFFER_SIZE],
+    /// Number of buffered samples
+    len: usize,
+    /// Index of the next sample to write
+    next: usize,
+}
+
+impl ArmedTrigger {
+    /// Arm a trigger at `threshold`, buffering samples over `pre_trigger`
+    pub fn new(threshold: f32, pre_trigger: Duration) -> Self {
+        Self {
+            threshold,
+            pre_trigger: pre_trigger.min(MAX_PRE_TRIGGER_TIME),
+            samples: [(0.0, 0); BUFFER_SIZE],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Check if the trigger has the given threshold and pre-trigger time
+    pub fn is_armed_with(&self, threshold: f32, pre_trigger: Duration) -> bool {
+        self.threshold == threshold && self.pre_trigger == pre_trigger.min(MAX_PRE_TRIGGER_TIME)
+    }
+
+    /// Add a sample taken at `timestamp` in microseconds, returning true if it is the
+    /// force onset. The onset sample is not buffered.
+    pub fn update(&mut self, weight: f32, timestamp: u64) -> bool {
+        if weight >= self.threshold {
+            return true;
+        }
+        self.samples[self.next] = (weight, timestamp);
+        self.next = (self.next + 1) % BUFFER_SIZE;
+        self.len = (self.len + 1).min(BUFFER_SIZE);
+        false
+    }
+
+    /// Buffered samples within the pre-trigger time before the onset at `onset` in
+    /// microseconds, oldest first, as (weight, microseconds before the onset)
+    pub fn pre_trigger_samples(&self, onset: u64) -> impl Iterator<Item = (f32, u32)> + '_ {
+        let window = self.pre_trigger.as_micros();
+        let oldest = (self.next + BUFFER_SIZE - self.len) % BUFFER_SIZE;
+        (0..self.len)
+            .map(move |i| self.samples[(oldest + i) % BUFFER_SIZE])
+            .map(move |(weight, timestamp)| (weight, onset.saturating_sub(timestamp)))
+            .filter(move |&(_, before)| before <= window)
+            .map(|(weight, before)| (weight, before as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onset_at_threshold() {
+        let mut trigger = ArmedTrigger::new(2.0, DEFAULT_PRE_TRIGGER_TIME);
+        assert!(!trigger.update(1.9, 0));
+        assert!(trigger.update(2.0, 12_500));
+    }
+
+    #[test]
+    fn pre_trigger_samples_within_window_oldest_first() {
+        let mut trigger = ArmedTrigger::new(2.0, Duration::from_millis(25));
+        for (i, weight) in [0.1, 0.2, 0.3, 0.4].into_iter().enumerate() {
+            trigger.update(weight, 12_500 * i as u64);
+        }
+        let samples: [(f32, u32); 2] = {
+            let mut samples = trigger.pre_trigger_samples(50_000);
+            [samples.next().unwrap(), samples.next().unwrap()]
+        };
+        assert_eq!(samples, [(0.3, 25_000), (0.4, 12_500)]);
+        assert_eq!(trigger.pre_trigger_samples(50_000).count(), 2);
+    }
+
+    #[test]
+    fn buffer_keeps_latest_samples() {
+        let mut trigger = ArmedTrigger::new(2.0, MAX_PRE_TRIGGER_TIME);
+        for i in 0..2 * BUFFER_SIZE as u64 {
+            trigger.update(0.0, 1_000 * i);
+        }
+        let onset = 2_000 * BUFFER_SIZE as u64;
+        let mut samples = trigger.pre_trigger_samples(onset);
+        assert_eq!(samples.next(), Some((0.0, 1_000 * BUFFER_SIZE as u32)));
+        assert_eq!(samples.count(), BUFFER_SIZE - 1);
+    }
+
+    #[test]
+    fn pre_trigger_time_is_capped() {
+        let trigger = ArmedTrigger::new(2.0, Duration::from_secs(5));
+        assert!(trigger.is_armed_with(2.0, MAX_PRE_TRIGGER_TIME));
+        assert!(trigger.is_armed_with(2.0, Duration::from_secs(10)));
+        assert!(!trigger.is_armed_with(3.0, MAX_PRE_TRIGGER_TIME));
+    }
+}
@@ -0,0 +1,309 @@
+/// Interval workouts
+///
+/// Repeater workouts uploaded by the client and timed on the device: sets of reps, each
+/// a work phase followed by a rest, with a longer rest between sets. Phase boundaries
+/// follow the device clock, so they do not depend on the phone scheduling. The force
+/// of each work phase is compared with the target force of the workout.
+use arrayvec::ArrayVec;
+use defmt::Format;
+use embassy_time::{Duration, Instant};
+
+/// Maximum number of events caused by a single sample
+const MAX_EVENTS: usize = 2;
+
+/// Workout definition
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub struct WorkoutDefinition {
+    /// Number of reps in a set
+    pub reps: u8,
+    /// Number of sets
+    pub sets: u8,
+    /// Duration of the work phase of a rep
+    pub work_time: Duration,
+    /// Rest between the reps of a set
+    pub rest_time: Duration,
+    /// Rest between sets
+    pub set_rest_time: Duration,
+    /// Force to reach during the work phases, in kg
+    pub target_force: f32,
+}
+
+impl WorkoutDefinition {
+    /// Check if the definition is within the supported ranges
+    pub fn is_valid(&self) -> bool {
+        self.reps > 0
+            && self.sets > 0
+            && self.work_time > Duration::from_ticks(0)
+            && self.target_force.is_finite()
+            && self.target_force > 0.0
+    }
+
+    /// Deserialize a definition from reps (u8), sets (u8), work, rest and set rest times
+    /// in seconds (`u16`) and target force in kg (`f32`), little-endian. Returns None if
+    /// it is invalid.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let seconds = |offset: usize| -> Option<Duration> {
+            let bytes = bytes.get(offset..offset + 2)?.try_into().ok()?;
+            Some(Duration::from_secs(u16::from_le_bytes(bytes) as u64))
+        };
+        let definition = Self {
+            reps: *bytes.first()?,
+            sets: *bytes.get(1)?,
+            work_time: seconds(2)?,
+            rest_time: seconds(4)?,
+            set_rest_time: seconds(6)?,
+            target_force: f32::from_le_bytes(bytes.get(8..12)?.try_into().ok()?),
+        };
+        definition.is_valid().then_some(definition)
+    }
+}
+
+/// Phase of a workout
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+#[repr(u8)]
+pub enum WorkoutPhase {
+    /// Pull at the target force
+    Work = 0,
+    /// Rest between reps
+    Rest = 1,
+    /// Rest between sets
+    SetRest = 2,
+    /// Every set was completed
+    Completed = 3,
+}
+
+/// Progress of the workout to report to the client
+#[derive(Clone, Copy, Debug, PartialEq, Format)]
+pub enum WorkoutEvent {
+    /// A phase starts now for the given set and rep indices, lasting the given duration
+    Phase(u8, u8, WorkoutPhase, Duration),
+    /// Work phase completed for the given set and rep indices, with its mean force in
+    /// kg and the percentage of its samples reaching the target force
+    Rep(u8, u8, f32, u8),
+}
+
+/// Workout in progress
+#[derive(Clone, Copy, Debug)]
+pub struct Workout {
+    definition: WorkoutDefinition,
+    /// End of the current phase, None until the first sample
+    phase_end: Option<Instant>,
+    /// Index of the current set
+    set: u8,
+    /// Index of the current rep in the set
+    rep: u8,
+    /// Current phase
+    phase: WorkoutPhase,
+    /// Sum of the samples of the current work phase
+    sum: f32,
+    /// Number of samples of the current work phase
+    count: u32,
+    /// Number of samples of the current work phase reaching the target force
+    on_target: u32,
+}
+
+impl Workout {
+    /// Create a workout starting with the next sample
+    pub fn new(definition: WorkoutDefinition) -> Self {
+        Self {
+            definition,
+            phase_end: None,
+            set: 0,
+            rep: 0,
+            phase: WorkoutPhase::Work,
+            sum: 0.0,
+            count: 0,
+            on_target: 0,
+        }
+    }
+
+    /// Get the workout definition
+    pub fn definition(&self) -> WorkoutDefinition {
+        self.definition
+    }
+
+    /// Check if every set was completed
+    pub fn is_completed(&self) -> bool {
+        self.phase == WorkoutPhase::Completed
+    }
+
+    /// Add a sample taken at `time`, returning the events it causes
+    pub fn update(&mut self, weight: f32, time: Instant) -> ArrayVec<WorkoutEvent, MAX_EVENTS> {
+        let mut events = ArrayVec::new();
+        if self.is_completed() {
+            return events;
+        }
+        let Some(phase_end) = self.phase_end else {
+            events.push(self.enter(WorkoutPhase::Work, time));
+            self.add_sample(weight);
+            return events;
+        };
+        if time < phase_end {
+            self.add_sample(weight);
+            return events;
+        }
+
+        // The next phase starts at the end of this one, so late samples do not shift it
+        match self.phase {
+            WorkoutPhase::Work => {
+                events.push(self.complete_rep());
+                let next = if self.rep + 1 < self.definition.reps {
+                    WorkoutPhase::Rest
+                } else if self.set + 1 < self.definition.sets {
+                    WorkoutPhase::SetRest
+                } else {
+                    WorkoutPhase::Completed
+                };
+                events.push(self.enter(next, phase_end));
+            }
+            WorkoutPhase::Rest => {
+                self.rep += 1;
+                events.push(self.enter(WorkoutPhase::Work, phase_end));
+            }
+            WorkoutPhase::SetRest => {
+                self.set += 1;
+                self.rep = 0;
+                events.push(self.enter(WorkoutPhase::Work, phase_end));
+            }
+            WorkoutPhase::Completed => {}
+        }
+        self.add_sample(weight);
+        events
+    }
+
+    /// Start `phase` at `start`
+    fn enter(&mut self, phase: WorkoutPhase, start: Instant) -> WorkoutEvent {
+        let duration = match phase {
+            WorkoutPhase::Work => self.definition.work_time,
+            WorkoutPhase::Rest => self.definition.rest_time,
+            WorkoutPhase::SetRest => self.definition.set_rest_time,
+            WorkoutPhase::Completed => Duration::from_ticks(0),
+        };
+        self.phase = phase;
+        self.phase_end = Some(start + duration);
+        self.sum = 0.0;
+        self.count = 0;
+        self.on_target = 0;
+        WorkoutEvent::Phase(self.set, self.rep, phase, duration)
+    }
+
+    /// Accumulate a sample of a work phase
+    fn add_sample(&mut self, weight: f32) {
+        if self.phase == WorkoutPhase::Work {
+            self.sum += weight;
+            self.count += 1;
+            if weight >= self.definition.target_force {
+                self.on_target += 1;
+            }
+        }
+    }
+
+    /// Summarize the work phase that just ended
+    fn complete_rep(&self) -> WorkoutEvent {
+        let (mean, compliance) = if self.count > 0 {
+            (
+                self.sum / self.count as f32,
+                (self.on_target * 100 / self.count) as u8,
+            )
+        } else {
+            (0.0, 0)
+        };
+        WorkoutEvent::Rep(self.set, self.rep, mean, compliance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(reps: u8, sets: u8) -> WorkoutDefinition {
+        WorkoutDefinition {
+            reps,
+            sets,
+            work_time: Duration::from_secs(7),
+            rest_time: Duration::from_secs(3),
+            set_rest_time: Duration::from_secs(60),
+            target_force: 20.0,
+        }
+    }
+
+    fn at(seconds: u64) -> Instant {
+        Instant::from_secs(seconds)
+    }
+
+    #[test]
+    fn first_sample_starts_work() {
+        let mut workout = Workout::new(definition(2, 1));
+        let events = workout.update(0.0, at(0));
+        assert_eq!(
+            events.as_slice(),
+            [WorkoutEvent::Phase(
+                0,
+                0,
+                WorkoutPhase::Work,
+                Duration::from_secs(7)
+            )]
+        );
+    }
+
+    #[test]
+    fn work_phase_reports_mean_and_compliance() {
+        let mut workout = Workout::new(definition(2, 1));
+        workout.update(10.0, at(0));
+        workout.update(20.0, at(2));
+        workout.update(30.0, at(4));
+        workout.update(20.0, at(6));
+        let events = workout.update(0.0, at(7));
+        assert_eq!(
+            events.as_slice(),
+            [
+                WorkoutEvent::Rep(0, 0, 20.0, 75),
+                WorkoutEvent::Phase(0, 0, WorkoutPhase::Rest, Duration::from_secs(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn phases_follow_the_schedule() {
+        let mut workout = Workout::new(definition(1, 2));
+        workout.update(0.0, at(0));
+        // A late sample does not shift the following phases
+        let events = workout.update(0.0, at(8));
+        assert_eq!(
+            events[1],
+            WorkoutEvent::Phase(0, 0, WorkoutPhase::SetRest, Duration::from_secs(60))
+        );
+        let events = workout.update(0.0, at(67));
+        assert_eq!(
+            events.as_slice(),
+            [WorkoutEvent::Phase(
+                1,
+                0,
+                WorkoutPhase::Work,
+                Duration::from_secs(7)
+            )]
+        );
+        let events = workout.update(0.0, at(74));
+        assert_eq!(
+            events[1],
+            WorkoutEvent::Phase(1, 0, WorkoutPhase::Completed, Duration::from_ticks(0))
+        );
+        assert!(workout.is_completed());
+        assert!(workout.update(0.0, at(75)).is_empty());
+    }
+
+    #[test]
+    fn definition_from_bytes() {
+        let mut bytes = [0u8; 12];
+        bytes[..8].copy_from_slice(&[3, 2, 7, 0, 3, 0, 60, 0]);
+        bytes[8..].copy_from_slice(&20.0f32.to_le_bytes());
+        assert_eq!(
+            WorkoutDefinition::from_bytes(&bytes),
+            Some(definition(3, 2))
+        );
+
+        bytes[0] = 0;
+        assert_eq!(WorkoutDefinition::from_bytes(&bytes), None);
+        assert_eq!(WorkoutDefinition::from_bytes(&bytes[..11]), None);
+    }
+}